@@ -0,0 +1,154 @@
+use std::fmt;
+
+// Julian date of 2000-01-01 12:00 TT, the epoch of the J2000 elements
+pub const J2000: f64 = 2451545.0;
+pub const DAYS_PER_CENTURY: f64 = 36525.0;
+
+// A Gregorian calendar date and time of day (UTC, the difference to TT does not show)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CalendarDate {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+}
+
+impl CalendarDate {
+    // Days since noon of 4713-01-01 BC, with the Julian calendar used before
+    // the Gregorian reform of 1582 like astronomers do (Meeus, chapter 7)
+    pub fn julian_date(&self) -> f64 {
+        let (mut year, mut month) = (self.year as f64, self.month as f64);
+        if month <= 2.0 {
+            year -= 1.0;
+            month += 12.0;
+        }
+        let day = self.day as f64 + (self.hour as f64 + self.minute as f64 / 60.0) / 24.0;
+        let gregorian = (self.year, self.month, self.day) >= (1582, 10, 15);
+        let correction = if gregorian {
+            let century = (year / 100.0).floor();
+            2.0 - century + (century / 4.0).floor()
+        } else {
+            0.0
+        };
+        (365.25 * (year + 4716.0)).floor() + (30.6001 * (month + 1.0)).floor() + day + correction - 1524.5
+    }
+
+    pub fn from_julian_date(julian_date: f64) -> Self {
+        // Days start at midnight, half a day before the Julian date's noon. Rounded
+        // to the minute first so 23:59:59.9 shows as the next day.
+        let from_midnight = ((julian_date + 0.5) * 1440.0).round() / 1440.0;
+        let whole = from_midnight.floor();
+        let minutes = ((from_midnight - whole) * 1440.0).round() as u32;
+        let a = if whole < 2299161.0 {
+            whole
+        } else {
+            let alpha = ((whole - 1867216.25) / 36524.25).floor();
+            whole + 1.0 + alpha - (alpha / 4.0).floor()
+        };
+        let b = a + 1524.0;
+        let c = ((b - 122.1) / 365.25).floor();
+        let d = (365.25 * c).floor();
+        let e = ((b - d) / 30.6001).floor();
+        let day = (b - d - (30.6001 * e).floor()) as u32;
+        let month = if e < 14.0 { e - 1.0 } else { e - 13.0 } as u32;
+        let year = if month > 2 { c - 4716.0 } else { c - 4715.0 } as i32;
+        CalendarDate {
+            year,
+            month,
+            day,
+            hour: minutes / 60,
+            minute: minutes % 60,
+        }
+    }
+
+    // YYYY-MM-DD, optionally followed by a T or a space and HH:MM
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid date '{text}', expected YYYY-MM-DD or YYYY-MM-DDTHH:MM");
+        let (date, time) = match text.trim().split_once(['T', ' ']) {
+            Some((date, time)) => (date, Some(time)),
+            None => (text.trim(), None),
+        };
+        // A leading minus is a year before 1 AD, not a separator
+        let (sign, date) = match date.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, date),
+        };
+        let mut fields = date.split('-').map(|field| field.parse::<u32>().map_err(|_| invalid()));
+        let (Some(year), Some(month), Some(day), None) = (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            return Err(invalid());
+        };
+        let (year, month, day) = (sign * year? as i32, month?, day?);
+        let (hour, minute) = match time {
+            Some(time) => {
+                let (hour, minute) = time.split_once(':').ok_or_else(invalid)?;
+                (hour.parse().map_err(|_| invalid())?, minute.parse().map_err(|_| invalid())?)
+            }
+            None => (0, 0),
+        };
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) || hour > 23 || minute > 59 {
+            return Err(invalid());
+        }
+        Ok(CalendarDate { year, month, day, hour, minute })
+    }
+}
+
+impl fmt::Display for CalendarDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute
+        )
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0 || year < 1583) => 29,
+        2 => 28,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> CalendarDate {
+        CalendarDate::parse(text).unwrap()
+    }
+
+    #[test]
+    fn known_dates_have_their_julian_dates() {
+        // Examples from Meeus, Astronomical Algorithms
+        let cases = [
+            ("2000-01-01T12:00", J2000),
+            ("1957-10-04T19:26", 2436116.30972),
+            ("1987-01-27", 2446822.5),
+            ("1600-12-31", 2305812.5),
+            ("0837-04-10T07:12", 2026871.8),
+            ("-1000-07-12T12:00", 1356001.0),
+        ];
+        for (text, expected) in cases {
+            assert!((date(text).julian_date() - expected).abs() < 1e-5, "{text}");
+        }
+    }
+
+    #[test]
+    fn julian_dates_convert_back() {
+        for text in ["2000-01-01 12:00", "1582-10-04 23:59", "1582-10-15 00:00", "2024-02-29 06:30", "1066-10-14 09:00"] {
+            assert_eq!(CalendarDate::from_julian_date(date(text).julian_date()).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn malformed_dates_are_rejected() {
+        for text in ["2024-13-01", "2023-02-29", "2024-04-31", "2024-04", "2024-04-08T25:00", "yesterday"] {
+            assert!(CalendarDate::parse(text).is_err(), "{text}");
+        }
+        assert_eq!(date("2024-04-08T18:17"), CalendarDate { year: 2024, month: 4, day: 8, hour: 18, minute: 17 });
+    }
+}
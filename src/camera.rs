@@ -1,4 +1,5 @@
 use nalgebra_glm::Vec3;
+use crate::celestial_bodies::CelestialBody;
 use std::f32::consts::PI;
 
 pub struct Camera {
@@ -45,4 +46,25 @@ impl Camera {
     self.eye += direction * delta;
     self.has_changed = true;
   }
-}
\ No newline at end of file
+
+  // Locks the camera above the orbital plane, looking at the sun
+  pub fn birds_eye(&mut self) {
+    self.center = Vec3::new(0.0, 0.0, 0.0);
+    self.eye = Vec3::new(40.0, 40.0, 0.0);
+    self.has_changed = true;
+  }
+
+  // Keeps the body centered, at a distance proportional to its size
+  pub fn focus_on(&mut self, body: &CelestialBody) {
+    let inverse_scale = body.scale;
+    let direction = (self.eye - self.center).normalize();
+    self.center = body.translation;
+    self.eye = body.translation
+      + Vec3::new(
+        (direction.x + 5.0) * inverse_scale,
+        1.5 * inverse_scale,
+        direction.z * inverse_scale,
+      );
+    self.has_changed = true;
+  }
+}
@@ -0,0 +1,160 @@
+use nalgebra_glm::Vec3;
+
+use crate::calendar::CalendarDate;
+use crate::loaders::scene_file::DEFAULT_SCENE;
+
+pub const USAGE: &str = "\
+Usage: space_travel_cg [--headless] [--scene <path>] [--date <date>] [headless options] [--help]
+
+Without --headless the interactive window is opened. The options can come in any order.
+
+Options:
+  --scene <path>         Scene file to load
+                         (default ./assets/scenes/solar_system.toml)
+  --date <date>          Show the planets where they were on this date,
+                         YYYY-MM-DD or YYYY-MM-DDTHH:MM (UTC)
+
+Headless options:
+  --ticks <n>            Simulation steps before the first frame (default 0)
+  --frames <n>           Number of consecutive frames to write (default 1)
+  --resolution <WxH>     Framebuffer size (default 800x600)
+  --eye <x,y,z>          Camera position
+  --center <x,y,z>       Point the camera looks at
+  --focus <body id>      Frame a body the same way the number keys do
+  --birds-eye            Use the birds-eye camera
+  --gravity              Move the bodies with the N-body simulation and
+                         report its energy and momentum drift
+  --output <path>        PNG file to write (default frame.png); with several
+                         frames a _NNNN suffix is added before the extension";
+
+#[derive(Clone, Debug)]
+pub struct HeadlessOptions {
+    pub ticks: u32,
+    pub frames: u32,
+    pub width: usize,
+    pub height: usize,
+    pub eye: Option<Vec3>,
+    pub center: Option<Vec3>,
+    pub focus: Option<String>,
+    pub birds_eye: bool,
+    pub gravity: bool,
+    pub output: String,
+    pub scene: String,
+    // Julian date
+    pub date: Option<f64>,
+}
+
+impl Default for HeadlessOptions {
+    fn default() -> Self {
+        HeadlessOptions {
+            ticks: 0,
+            frames: 1,
+            width: 800,
+            height: 600,
+            eye: None,
+            center: None,
+            focus: None,
+            birds_eye: false,
+            gravity: false,
+            output: "frame.png".to_string(),
+            scene: DEFAULT_SCENE.to_string(),
+            date: None,
+        }
+    }
+}
+
+pub enum Mode {
+    Window { scene: String, date: Option<f64> },
+    Headless(HeadlessOptions),
+    Help,
+}
+
+pub fn parse_args(args: &[String]) -> Result<Mode, String> {
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        return Ok(Mode::Help);
+    }
+    // --headless switches the mode wherever it appears, the other flags depend on it
+    let headless = args.iter().any(|arg| arg == "--headless");
+
+    let mut options = HeadlessOptions::default();
+    let mut rest = args.iter().filter(|arg| *arg != "--headless");
+    while let Some(flag) = rest.next() {
+        let mut value = || {
+            rest.next()
+                .ok_or_else(|| format!("missing value for {flag}"))
+        };
+        match flag.as_str() {
+            "--scene" => options.scene = value()?.clone(),
+            "--date" => options.date = Some(CalendarDate::parse(value()?)?.julian_date()),
+            // The remaining flags only make sense without a window
+            _ if !headless => return Err(format!("unknown argument '{flag}'")),
+            "--ticks" => options.ticks = parse_number(flag, value()?)?,
+            "--frames" => options.frames = parse_number(flag, value()?)?,
+            "--resolution" => (options.width, options.height) = parse_resolution(value()?)?,
+            "--eye" => options.eye = Some(parse_vec3(flag, value()?)?),
+            "--center" => options.center = Some(parse_vec3(flag, value()?)?),
+            "--focus" => options.focus = Some(value()?.clone()),
+            "--birds-eye" => options.birds_eye = true,
+            "--gravity" => options.gravity = true,
+            "--output" => options.output = value()?.clone(),
+            _ => return Err(format!("unknown argument '{flag}'")),
+        }
+    }
+    if !headless {
+        return Ok(Mode::Window { scene: options.scene, date: options.date });
+    }
+    if options.frames == 0 {
+        return Err("--frames must be at least 1".to_string());
+    }
+    Ok(Mode::Headless(options))
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{value}' for {flag}"))
+}
+
+fn parse_resolution(value: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("invalid resolution '{value}', expected WIDTHxHEIGHT");
+    let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+    let width: usize = width.parse().map_err(|_| invalid())?;
+    let height: usize = height.parse().map_err(|_| invalid())?;
+    if width == 0 || height == 0 {
+        return Err(invalid());
+    }
+    Ok((width, height))
+}
+
+fn parse_vec3(flag: &str, value: &str) -> Result<Vec3, String> {
+    let components: Vec<f32> = value
+        .split(',')
+        .map(|c| c.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("invalid vector '{value}' for {flag}, expected x,y,z"))?;
+    match components[..] {
+        [x, y, z] => Ok(Vec3::new(x, y, z)),
+        _ => Err(format!("invalid vector '{value}' for {flag}, expected x,y,z")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn headless_can_come_anywhere() {
+        for line in ["--headless --scene x.toml --ticks 5", "--scene x.toml --ticks 5 --headless"] {
+            match parse_args(&args(line)) {
+                Ok(Mode::Headless(options)) => assert!(options.scene == "x.toml" && options.ticks == 5),
+                _ => panic!("'{line}' did not parse as headless"),
+            }
+        }
+        assert!(matches!(parse_args(&args("--scene x.toml")), Ok(Mode::Window { .. })));
+        assert!(parse_args(&args("--scene x.toml --ticks 5")).is_err());
+    }
+}
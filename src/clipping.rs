@@ -0,0 +1,104 @@
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+use crate::vertex::Vertex;
+
+// Which homogeneous clip-space planes triangles are clipped against
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClipMode {
+    Near,
+    Frustum,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ClipPlane {
+    Near,
+    Far,
+    Left,
+    Right,
+    Bottom,
+    Top,
+}
+
+const NEAR_PLANES: [ClipPlane; 1] = [ClipPlane::Near];
+const FRUSTUM_PLANES: [ClipPlane; 6] = [
+    ClipPlane::Near,
+    ClipPlane::Far,
+    ClipPlane::Left,
+    ClipPlane::Right,
+    ClipPlane::Bottom,
+    ClipPlane::Top,
+];
+
+impl ClipPlane {
+    // Signed distance in clip space, positive means inside (-w <= x,y,z <= w)
+    fn distance(&self, p: &Vec4) -> f32 {
+        match self {
+            ClipPlane::Near => p.z + p.w,
+            ClipPlane::Far => p.w - p.z,
+            ClipPlane::Left => p.x + p.w,
+            ClipPlane::Right => p.w - p.x,
+            ClipPlane::Bottom => p.y + p.w,
+            ClipPlane::Top => p.w - p.y,
+        }
+    }
+}
+
+// Sutherland-Hodgman clipping of a triangle in clip space.
+// Returns the clipped convex polygon, empty if the triangle is fully outside.
+pub fn clip_triangle(triangle: [&Vertex; 3], mode: ClipMode) -> Vec<Vertex> {
+    let planes: &[ClipPlane] = match mode {
+        ClipMode::Near => &NEAR_PLANES,
+        ClipMode::Frustum => &FRUSTUM_PLANES,
+    };
+
+    let mut polygon: Vec<Vertex> = triangle.into_iter().cloned().collect();
+    for plane in planes {
+        if polygon.is_empty() {
+            break;
+        }
+        polygon = clip_polygon(&polygon, plane);
+    }
+    polygon
+}
+
+fn clip_polygon(polygon: &[Vertex], plane: &ClipPlane) -> Vec<Vertex> {
+    let mut output = Vec::with_capacity(polygon.len() + 1);
+    for i in 0..polygon.len() {
+        let current = &polygon[i];
+        let next = &polygon[(i + 1) % polygon.len()];
+        let d_current = plane.distance(&current.clip_position);
+        let d_next = plane.distance(&next.clip_position);
+
+        if d_current >= 0.0 {
+            output.push(current.clone());
+        }
+        if (d_current >= 0.0) != (d_next >= 0.0) {
+            let t = d_current / (d_current - d_next);
+            output.push(current.lerp(next, t));
+        }
+    }
+    output
+}
+
+// Splits a convex polygon into a triangle fan
+pub fn triangulate(polygon: &[Vertex]) -> Vec<[Vertex; 3]> {
+    let mut triangles = Vec::new();
+    for i in 1..polygon.len().saturating_sub(1) {
+        triangles.push([
+            polygon[0].clone(),
+            polygon[i].clone(),
+            polygon[i + 1].clone(),
+        ]);
+    }
+    triangles
+}
+
+// Perspective division and viewport mapping of an already clipped vertex
+pub fn to_screen(vertex: &mut Vertex, viewport_matrix: &Mat4) {
+    let clip = vertex.clip_position;
+    let w = clip.w;
+    let ndc_position = Vec4::new(clip.x / w, clip.y / w, clip.z / w, 1.0);
+    let screen_position = viewport_matrix * ndc_position;
+    vertex.transformed_position = Vec3::new(screen_position.x, screen_position.y, screen_position.z);
+    // Kept for perspective-correct interpolation in the rasterizer
+    vertex.inv_w = 1.0 / w;
+}
@@ -0,0 +1,135 @@
+use std::time::Duration;
+
+// Simulation ticks per second of real time at a warp of 1, the rate the
+// 16 ms frame loop used to advance it at
+pub const TICKS_PER_SECOND: f64 = 60.0;
+
+// Warp factors go from 10^MIN_WARP_EXPONENT to 10^MAX_WARP_EXPONENT
+const MIN_WARP_EXPONENT: i32 = -2;
+const MAX_WARP_EXPONENT: i32 = 4;
+
+// A frame that took longer than this (the window being dragged, a breakpoint)
+// does not make the simulation jump ahead
+const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+
+// Turns real elapsed time into simulation ticks, independent of how fast
+// frames are drawn
+pub struct SimulationClock {
+    warp_exponent: i32,
+    reversed: bool,
+    paused: bool,
+    // Single ticks asked for while paused, run on the next advance
+    steps: u32,
+}
+
+impl SimulationClock {
+    pub fn new() -> Self {
+        SimulationClock {
+            warp_exponent: 0,
+            reversed: false,
+            paused: false,
+            steps: 0,
+        }
+    }
+
+    // Ticks per tick of the normal speed, negative when running backwards
+    pub fn warp(&self) -> f64 {
+        let warp = 10.0_f64.powi(self.warp_exponent);
+        if self.reversed {
+            -warp
+        } else {
+            warp
+        }
+    }
+
+    pub fn faster(&mut self) {
+        self.warp_exponent = (self.warp_exponent + 1).min(MAX_WARP_EXPONENT);
+    }
+
+    pub fn slower(&mut self) {
+        self.warp_exponent = (self.warp_exponent - 1).max(MIN_WARP_EXPONENT);
+    }
+
+    pub fn reverse(&mut self) {
+        self.reversed = !self.reversed;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    // Pauses and moves a single tick in the current direction
+    pub fn step(&mut self) {
+        self.paused = true;
+        self.steps += 1;
+    }
+
+    // Ticks to move the simulation by for `elapsed` real time, negative ones backwards
+    pub fn advance(&mut self, elapsed: Duration) -> f64 {
+        let direction = if self.reversed { -1.0 } else { 1.0 };
+        let steps = std::mem::take(&mut self.steps) as f64 * direction;
+        if self.paused {
+            return steps;
+        }
+        elapsed.min(MAX_FRAME_TIME).as_secs_f64() * TICKS_PER_SECOND * self.warp() + steps
+    }
+
+    // Indicator for the HUD, e.g. ">> x1000", "<< x0.1" or "|| x10"
+    pub fn label(&self) -> String {
+        let state = match (self.paused, self.reversed) {
+            (true, _) => "||",
+            (false, false) => ">>",
+            (false, true) => "<<",
+        };
+        let decimals = (-self.warp_exponent).max(0) as usize;
+        format!("{state} x{:.decimals$}", self.warp().abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_follow_real_time_and_the_warp() {
+        let mut clock = SimulationClock::new();
+        let frame = Duration::from_millis(50);
+        assert!((clock.advance(frame) - 3.0).abs() < 1e-9);
+
+        clock.faster();
+        clock.faster();
+        assert_eq!(clock.label(), ">> x100");
+        assert!((clock.advance(frame) - 300.0).abs() < 1e-9);
+
+        clock.reverse();
+        for _ in 0..5 {
+            clock.slower();
+        }
+        assert_eq!(clock.label(), "<< x0.01");
+        assert!((clock.advance(frame) + 0.03).abs() < 1e-9);
+        // A stalled frame counts as a short one
+        assert!((clock.advance(Duration::from_secs(10)) + 0.15).abs() < 1e-9);
+    }
+
+    #[test]
+    fn pausing_stops_time_except_for_single_steps() {
+        let mut clock = SimulationClock::new();
+        clock.toggle_pause();
+        assert_eq!(clock.label(), "|| x1");
+        assert_eq!(clock.advance(Duration::from_millis(50)), 0.0);
+
+        clock.step();
+        clock.step();
+        assert_eq!(clock.advance(Duration::from_millis(50)), 2.0);
+        assert_eq!(clock.advance(Duration::from_millis(50)), 0.0);
+        clock.reverse();
+        clock.step();
+        assert_eq!(clock.advance(Duration::from_millis(50)), -1.0);
+
+        // Stepping while running pauses first
+        let mut clock = SimulationClock::new();
+        clock.step();
+        assert_eq!(clock.advance(Duration::from_millis(50)), 1.0);
+        assert_eq!(clock.advance(Duration::from_millis(50)), 0.0);
+    }
+}
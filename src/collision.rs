@@ -0,0 +1,126 @@
+use std::fmt;
+
+use nalgebra_glm::Vec3;
+
+// What happens to the ship when it runs into a body
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CollisionResponse {
+    // Flies off the surface, keeping `restitution` of the speed it hit it with
+    Bounce,
+    // Comes to rest on the surface and moves along with the body
+    Stop,
+    // Goes back to where the scene file put it, at rest
+    Respawn,
+}
+
+// The [collisions] table of the scene file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CollisionSettings {
+    pub enabled: bool,
+    pub response: CollisionResponse,
+    // Fraction of the speed towards the surface that a bounce gives back, 0 to 1
+    pub restitution: f32,
+}
+
+impl Default for CollisionSettings {
+    fn default() -> Self {
+        CollisionSettings {
+            enabled: true,
+            response: CollisionResponse::Bounce,
+            restitution: 0.5,
+        }
+    }
+}
+
+// The ship ran into a body, reported to the HUD
+#[derive(Clone, Debug, PartialEq)]
+pub struct Collision {
+    pub body: String,
+    pub response: CollisionResponse,
+    // Speed towards the body's surface at the moment of contact
+    pub speed: f32,
+}
+
+impl fmt::Display for Collision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self.response {
+            CollisionResponse::Bounce => "Bounced off",
+            CollisionResponse::Stop => "Landed on",
+            CollisionResponse::Respawn => "Crashed into",
+        };
+        write!(f, "{what} {} at {:.3}", self.body, self.speed)
+    }
+}
+
+// Overlap of two bounding spheres
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    pub index: usize,
+    // From the other sphere's center towards the first one
+    pub normal: Vec3,
+    pub depth: f32,
+}
+
+// The deepest overlap between the sphere at `center` and `spheres`, given by
+// index, center and radius
+pub fn deepest_contact(
+    center: Vec3,
+    radius: f32,
+    spheres: impl IntoIterator<Item = (usize, Vec3, f32)>,
+) -> Option<Contact> {
+    spheres
+        .into_iter()
+        .filter_map(|(index, other, other_radius)| {
+            let offset = center - other;
+            let distance = offset.norm();
+            let depth = radius + other_radius - distance;
+            // Dead center there is no direction to push out in, up will do
+            let normal = if distance > 0.0 { offset / distance } else { Vec3::y() };
+            (depth > 0.0).then_some(Contact { index, normal, depth })
+        })
+        .max_by(|a, b| a.depth.total_cmp(&b.depth))
+}
+
+// Velocity after bouncing off a surface facing `normal` that moves at
+// `surface_velocity`. Moving away from it already, nothing changes.
+pub fn bounce(velocity: Vec3, surface_velocity: Vec3, normal: Vec3, restitution: f32) -> Vec3 {
+    let relative = velocity - surface_velocity;
+    let towards = relative.dot(&normal);
+    if towards >= 0.0 {
+        return velocity;
+    }
+    velocity - normal * ((1.0 + restitution) * towards)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_deepest_overlap_wins() {
+        let spheres = [
+            (0, Vec3::new(3.0, 0.0, 0.0), 1.0),
+            (1, Vec3::new(0.0, 1.9, 0.0), 1.0),
+            (2, Vec3::new(-1.8, 0.0, 0.0), 1.0),
+        ];
+        let contact = deepest_contact(Vec3::zeros(), 1.0, spheres).unwrap();
+        assert_eq!(contact.index, 2);
+        assert!((contact.depth - 0.2).abs() < 1e-6);
+        assert_eq!(contact.normal, Vec3::new(1.0, 0.0, 0.0));
+        // Spheres that only touch do not collide
+        assert!(deepest_contact(Vec3::zeros(), 1.0, [(0, Vec3::new(2.0, 0.0, 0.0), 1.0)]).is_none());
+    }
+
+    #[test]
+    fn bounces_reflect_the_speed_towards_the_surface() {
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let velocity = Vec3::new(1.0, -2.0, 0.0);
+        assert_eq!(bounce(velocity, Vec3::zeros(), normal, 1.0), Vec3::new(1.0, 2.0, 0.0));
+        assert_eq!(bounce(velocity, Vec3::zeros(), normal, 0.5), Vec3::new(1.0, 1.0, 0.0));
+        // A surface coming up faster than the ship falls still hits it
+        let surface = Vec3::new(0.0, 1.0, 0.0);
+        assert_eq!(bounce(velocity, surface, normal, 0.0), Vec3::new(1.0, 1.0, 0.0));
+        // Leaving the surface is left alone
+        assert_eq!(bounce(-velocity, Vec3::zeros(), normal, 1.0), -velocity);
+    }
+}
@@ -0,0 +1,33 @@
+use crate::bounding_box::edge_function;
+use crate::vertex::Vertex;
+
+// Which faces are discarded before rasterization
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CullMode {
+    Back,
+    // For meshes seen from the inside, like a skybox
+    Front,
+    None,
+}
+
+// Signed area of a screen-space triangle. The viewport flips y, so
+// counter-clockwise (front facing) triangles end up with a positive area.
+pub fn signed_area(triangle: &[Vertex; 3]) -> f32 {
+    edge_function(
+        &triangle[0].transformed_position,
+        &triangle[1].transformed_position,
+        &triangle[2].transformed_position,
+    )
+}
+
+pub fn is_culled(area: f32, mode: CullMode) -> bool {
+    // Degenerate triangles never cover a pixel
+    if area == 0.0 || area.is_nan() {
+        return true;
+    }
+    match mode {
+        CullMode::Back => area < 0.0,
+        CullMode::Front => area > 0.0,
+        CullMode::None => false,
+    }
+}
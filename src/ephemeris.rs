@@ -0,0 +1,185 @@
+use crate::calendar::{DAYS_PER_CENTURY, J2000};
+use crate::orbit::OrbitalElements;
+
+// The [ephemeris] table of the scene file. When enabled the planets of the
+// table below are placed where they really were, tick 0 being `start`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EphemerisSettings {
+    pub enabled: bool,
+    // Julian date
+    pub start: f64,
+    pub days_per_tick: f64,
+}
+
+impl Default for EphemerisSettings {
+    fn default() -> Self {
+        EphemerisSettings {
+            enabled: false,
+            start: J2000,
+            days_per_tick: 1.0,
+        }
+    }
+}
+
+// Mean orbital elements of a planet at J2000 and their change per Julian
+// century, in degrees. Values from E. M. Standish, "Keplerian Elements for
+// Approximate Positions of the Major Planets" (JPL), valid 1800-2050 AD and
+// good to a few arcminutes. The semi-major axes are left out, scenes keep
+// their own compressed distances.
+#[derive(Clone, Copy, Debug)]
+pub struct MeanElements {
+    pub eccentricity: [f64; 2],
+    pub inclination: [f64; 2],
+    pub mean_longitude: [f64; 2],
+    pub longitude_of_perihelion: [f64; 2],
+    pub ascending_node: [f64; 2],
+}
+
+// Body id in the scene files and its elements. The Earth row is the
+// Earth-Moon barycenter.
+pub const PLANETS: [(&str, MeanElements); 9] = [
+    ("mercury", MeanElements {
+        eccentricity: [0.20563593, 0.00001906],
+        inclination: [7.00497902, -0.00594749],
+        mean_longitude: [252.25032350, 149472.67411175],
+        longitude_of_perihelion: [77.45779628, 0.16047689],
+        ascending_node: [48.33076593, -0.12534081],
+    }),
+    ("venus", MeanElements {
+        eccentricity: [0.00677672, -0.00004107],
+        inclination: [3.39467605, -0.00078890],
+        mean_longitude: [181.97909950, 58517.81538729],
+        longitude_of_perihelion: [131.60246718, 0.00268329],
+        ascending_node: [76.67984255, -0.27769418],
+    }),
+    ("earth", MeanElements {
+        eccentricity: [0.01671123, -0.00004392],
+        inclination: [-0.00001531, -0.01294668],
+        mean_longitude: [100.46457166, 35999.37244981],
+        longitude_of_perihelion: [102.93768193, 0.32327364],
+        ascending_node: [0.0, 0.0],
+    }),
+    ("mars", MeanElements {
+        eccentricity: [0.09339410, 0.00007882],
+        inclination: [1.84969142, -0.00813131],
+        mean_longitude: [-4.55343205, 19140.30268499],
+        longitude_of_perihelion: [-23.94362959, 0.44441088],
+        ascending_node: [49.55953891, -0.29257343],
+    }),
+    ("jupiter", MeanElements {
+        eccentricity: [0.04838624, -0.00013253],
+        inclination: [1.30439695, -0.00183714],
+        mean_longitude: [34.39644051, 3034.74612775],
+        longitude_of_perihelion: [14.72847983, 0.21252668],
+        ascending_node: [100.47390909, 0.20469106],
+    }),
+    ("saturn", MeanElements {
+        eccentricity: [0.05386179, -0.00050991],
+        inclination: [2.48599187, 0.00193609],
+        mean_longitude: [49.95424423, 1222.49362201],
+        longitude_of_perihelion: [92.59887831, -0.41897216],
+        ascending_node: [113.66242448, -0.28867794],
+    }),
+    ("uranus", MeanElements {
+        eccentricity: [0.04725744, -0.00004397],
+        inclination: [0.77263783, -0.00242939],
+        mean_longitude: [313.23810451, 428.48202785],
+        longitude_of_perihelion: [170.95427630, 0.40805281],
+        ascending_node: [74.01692503, 0.04240589],
+    }),
+    ("neptune", MeanElements {
+        eccentricity: [0.00859048, 0.00005105],
+        inclination: [1.77004347, 0.00035372],
+        mean_longitude: [-55.12002969, 218.45945325],
+        longitude_of_perihelion: [44.96476227, -0.32241464],
+        ascending_node: [131.78422574, -0.00508664],
+    }),
+    ("pluto", MeanElements {
+        eccentricity: [0.24882730, 0.00005170],
+        inclination: [17.14001206, 0.00004818],
+        mean_longitude: [238.92903833, 145.20780515],
+        longitude_of_perihelion: [224.06891629, -0.04062942],
+        ascending_node: [110.30393684, -0.01183482],
+    }),
+];
+
+pub fn mean_elements(id: &str) -> Option<&'static MeanElements> {
+    PLANETS.iter().find(|(planet, _)| *planet == id).map(|(_, elements)| elements)
+}
+
+impl MeanElements {
+    // Osculating elements on `julian_date`, with time 0 of the returned orbit at
+    // that date and `days_per_tick` days for every tick after it. Distances are
+    // the scene's: `semi_major_axis` replaces the real one so the compressed
+    // layout keeps its spacing while the planet sits in the right direction.
+    pub fn orbit_at(&self, julian_date: f64, days_per_tick: f64, semi_major_axis: f32) -> OrbitalElements {
+        let centuries = (julian_date - J2000) / DAYS_PER_CENTURY;
+        let at = |[value, rate]: [f64; 2]| value + rate * centuries;
+        let perihelion = at(self.longitude_of_perihelion);
+        let node = at(self.ascending_node);
+        let mean_anomaly = at(self.mean_longitude) - perihelion;
+        // The mean anomaly advances with the mean longitude minus the perihelion's drift
+        let degrees_per_day = (self.mean_longitude[1] - self.longitude_of_perihelion[1]) / DAYS_PER_CENTURY;
+        OrbitalElements {
+            semi_major_axis,
+            eccentricity: at(self.eccentricity) as f32,
+            inclination: at(self.inclination).to_radians() as f32,
+            ascending_node: node.to_radians() as f32,
+            argument_of_periapsis: (perihelion - node).to_radians() as f32,
+            mean_anomaly_at_epoch: mean_anomaly.rem_euclid(360.0).to_radians() as f32,
+            mean_motion: (degrees_per_day * days_per_tick).to_radians() as f32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::CalendarDate;
+
+    fn longitude(position: nalgebra_glm::Vec3) -> f32 {
+        // World x and z are the ecliptic x and y
+        position.z.atan2(position.x).to_degrees().rem_euclid(360.0)
+    }
+
+    fn longitude_on(id: &str, date: &str) -> f32 {
+        let date = CalendarDate::parse(date).unwrap().julian_date();
+        longitude(mean_elements(id).unwrap().orbit_at(date, 1.0, 10.0).position(0.0))
+    }
+
+    fn degrees_apart(a: f32, b: f32) -> f32 {
+        ((a - b + 180.0).rem_euclid(360.0) - 180.0).abs()
+    }
+
+    #[test]
+    fn planets_line_up_on_known_dates() {
+        // At the March equinox the sun is at longitude 0, so the earth is at 180
+        assert!(degrees_apart(longitude_on("earth", "2001-03-20T13:31"), 180.0) < 0.5);
+        // Oppositions and inferior conjunctions put a planet and the earth in
+        // the same direction from the sun
+        let events = [
+            ("mercury", "2024-04-11T23:00", 2.0),
+            ("venus", "2022-01-09T02:00", 1.0),
+            ("mars", "2020-10-13T23:00", 1.0),
+            ("jupiter", "2023-11-03T05:00", 1.0),
+            ("saturn", "2023-08-27T08:00", 1.0),
+        ];
+        for (id, date, tolerance) in events {
+            let apart = degrees_apart(longitude_on(id, date), longitude_on("earth", date));
+            assert!(apart < tolerance, "{id} on {date}: {apart}");
+        }
+    }
+
+    #[test]
+    fn the_clock_turns_the_earth_once_a_year() {
+        let start = CalendarDate::parse("2001-03-20").unwrap().julian_date();
+        let earth = mean_elements("earth").unwrap();
+        let orbit = earth.orbit_at(start, 2.0, 10.0);
+        // A sidereal year later at 2 days per tick, the Earth is back where it started
+        let year = 365.256 / 2.0;
+        assert!((orbit.position(0.0) - orbit.position(year)).norm() < 0.05);
+        // And evaluating the table half way agrees with running the clock
+        let later = earth.orbit_at(start + 100.0, 2.0, 10.0);
+        assert!((later.position(0.0) - orbit.position(50.0)).norm() < 1e-2);
+    }
+}
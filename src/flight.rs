@@ -0,0 +1,226 @@
+use nalgebra_glm::Vec3;
+
+use crate::celestial_bodies::CelestialBody;
+
+// The longest step the ship is integrated with, in ticks. Warped frames are
+// split into steps this long, at most MAX_STEPS of them.
+const MAX_STEP: f32 = 0.25;
+const MAX_STEPS: u32 = 4000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FlightMode {
+    // Velocity and turning die down on their own and nothing pulls on the ship
+    Arcade,
+    // The ship keeps what it has and falls towards every body with a mass
+    Realistic,
+}
+
+// The [flight] table of the scene file, accelerations in scene units per tick²
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlightSettings {
+    // Mode the ship starts in, the M key switches it in the window
+    pub mode: FlightMode,
+    pub main_thrust: f32,
+    // Thrusters moving the ship sideways and up or down
+    pub rcs_thrust: f32,
+    // In radians per tick²
+    pub turn_acceleration: f32,
+    // Fraction of the velocity and turn rate lost per tick in arcade mode
+    pub damping: f32,
+}
+
+impl Default for FlightSettings {
+    fn default() -> Self {
+        FlightSettings {
+            mode: FlightMode::Arcade,
+            main_thrust: 0.02,
+            rcs_thrust: 0.01,
+            turn_acceleration: 0.01,
+            damping: 0.1,
+        }
+    }
+}
+
+// What the pilot asks for, each from -1 to 1
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Controls {
+    // Main engine, negative is the retro thrusters
+    pub main: f32,
+    // Right is positive
+    pub strafe: f32,
+    pub lift: f32,
+    // Left (counterclockwise seen from above) is positive
+    pub turn: f32,
+}
+
+// Motion of the player's ship. The ship turns around its vertical axis only,
+// its heading is the body's rotation around y.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ship {
+    pub mode: FlightMode,
+    // Relative to the ship's parent, in scene units per tick
+    pub velocity: Vec3,
+    // Radians per tick
+    pub turn_rate: f32,
+}
+
+impl Ship {
+    pub fn new(settings: &FlightSettings) -> Self {
+        Ship {
+            mode: settings.mode,
+            velocity: Vec3::zeros(),
+            turn_rate: 0.0,
+        }
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            FlightMode::Arcade => FlightMode::Realistic,
+            FlightMode::Realistic => FlightMode::Arcade,
+        };
+    }
+
+    pub fn stop(&mut self) {
+        self.velocity = Vec3::zeros();
+        self.turn_rate = 0.0;
+    }
+
+    // Moves `body` for `ticks` under the controls. The pilot always flies
+    // forwards in time, running the clock backwards only rewinds the bodies.
+    // `attractors` are the world position and G·mass of the bodies that pull
+    // on the ship in realistic mode; they stay put during the call.
+    pub fn fly(
+        &mut self,
+        body: &mut CelestialBody,
+        controls: &Controls,
+        settings: &FlightSettings,
+        attractors: &[(Vec3, f32)],
+        ticks: f32,
+    ) {
+        let ticks = ticks.abs();
+        let steps = (ticks / MAX_STEP).ceil().min(MAX_STEPS as f32) as u32;
+        if steps == 0 {
+            return;
+        }
+        let dt = ticks / steps as f32;
+        // World position of what `position` is relative to
+        let origin = body.translation - body.position;
+        for _ in 0..steps {
+            let heading = body.rotation.y;
+            let forward = Vec3::new(-heading.cos(), 0.0, heading.sin());
+            let right = Vec3::new(-heading.sin(), 0.0, -heading.cos());
+            let mut acceleration = forward * (controls.main.clamp(-1.0, 1.0) * settings.main_thrust)
+                + right * (controls.strafe.clamp(-1.0, 1.0) * settings.rcs_thrust)
+                + Vec3::y() * (controls.lift.clamp(-1.0, 1.0) * settings.rcs_thrust);
+            if self.mode == FlightMode::Realistic {
+                acceleration += pull(origin + body.position, attractors);
+            }
+            // Semi-implicit Euler: the velocity first, then the position with the new velocity
+            self.velocity += acceleration * dt;
+            self.turn_rate += controls.turn.clamp(-1.0, 1.0) * settings.turn_acceleration * dt;
+            if self.mode == FlightMode::Arcade {
+                let kept = (1.0 - settings.damping).powf(dt);
+                self.velocity *= kept;
+                self.turn_rate *= kept;
+            }
+            body.position += self.velocity * dt;
+            body.rotation.y += self.turn_rate * dt;
+        }
+        body.translation = origin + body.position;
+    }
+}
+
+// Gravitational acceleration at `position`
+fn pull(position: Vec3, attractors: &[(Vec3, f32)]) -> Vec3 {
+    let mut acceleration = Vec3::zeros();
+    for (center, gm) in attractors {
+        let offset = center - position;
+        let distance_squared = offset.norm_squared();
+        if distance_squared > 0.0 {
+            acceleration += offset * (gm / (distance_squared * distance_squared.sqrt()));
+        }
+    }
+    acceleration
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loaders::scene_file::parse_scene;
+
+    fn ship_body() -> CelestialBody {
+        let source = "[meshes]\nship = \"ship.obj\"\n[[bodies]]\nid = \"spaceship\"\nmesh = \"ship\"\nposition = [10.0, 0.0, 0.0]\n";
+        parse_scene(source, "test.toml").unwrap().bodies.remove(0)
+    }
+
+    #[test]
+    fn arcade_mode_settles_at_a_top_speed_and_coasts_to_a_stop() {
+        let settings = FlightSettings::default();
+        let mut ship = Ship::new(&settings);
+        let mut body = ship_body();
+        ship.fly(&mut body, &Controls { main: 1.0, ..Controls::default() }, &settings, &[], 200.0);
+        // Thrust and damping balance out at a / -ln(1 - d) per tick, up to the step size
+        let top_speed = settings.main_thrust / -(1.0 - settings.damping).ln();
+        assert!((ship.velocity.norm() - top_speed).abs() < 0.03 * top_speed, "{}", ship.velocity.norm());
+        ship.fly(&mut body, &Controls { turn: 1.0, ..Controls::default() }, &settings, &[], 200.0);
+        let top_turn_rate = settings.turn_acceleration / -(1.0 - settings.damping).ln();
+        assert!((ship.turn_rate - top_turn_rate).abs() < 0.03 * top_turn_rate, "{}", ship.turn_rate);
+
+        ship.fly(&mut body, &Controls::default(), &settings, &[], 200.0);
+        assert!(ship.velocity.norm() < 1e-6 && ship.turn_rate.abs() < 1e-6);
+    }
+
+    #[test]
+    fn reversed_time_still_flies_the_ship_forwards() {
+        let settings = FlightSettings::default();
+        let mut forwards = Ship::new(&settings);
+        let mut backwards = Ship::new(&settings);
+        let (mut body, mut reversed_body) = (ship_body(), ship_body());
+        let full = Controls { main: 1.0, turn: 1.0, ..Controls::default() };
+        for _ in 0..100 {
+            forwards.fly(&mut body, &full, &settings, &[], 170.0);
+            backwards.fly(&mut reversed_body, &full, &settings, &[], -170.0);
+        }
+        // Damping keeps damping and thrust keeps pushing the way the nose points
+        assert!(backwards.velocity.iter().all(|v| v.is_finite()) && reversed_body.position.iter().all(|p| p.is_finite()));
+        assert_eq!(backwards, forwards);
+        assert!(backwards.velocity.norm() < settings.main_thrust / -(1.0 - settings.damping).ln() * 1.03);
+    }
+
+    #[test]
+    fn the_main_engine_pushes_along_the_heading() {
+        let settings = FlightSettings::default();
+        let mut ship = Ship::new(&settings);
+        let mut body = ship_body();
+        // The nose of the model points along -x, a quarter turn left points it along +z
+        body.rotation.y = std::f32::consts::FRAC_PI_2;
+        let controls = Controls { main: 1.0, strafe: 1.0, ..Controls::default() };
+        ship.fly(&mut body, &controls, &settings, &[], 1.0);
+        assert!(ship.velocity.z > 0.0 && ship.velocity.x < 0.0 && ship.velocity.y == 0.0);
+        // Right of +z is -x
+        assert!((ship.velocity.z * settings.rcs_thrust + ship.velocity.x * settings.main_thrust).abs() < 1e-6);
+        assert_eq!(body.translation, body.position);
+    }
+
+    #[test]
+    fn realistic_mode_falls_into_a_circular_orbit() {
+        let settings = FlightSettings { mode: FlightMode::Realistic, ..FlightSettings::default() };
+        let mut ship = Ship::new(&settings);
+        let mut body = ship_body();
+        let gm = 0.221;
+        // Launched sideways at the circular speed around a star at the origin
+        ship.velocity = Vec3::new(0.0, 0.0, (gm / 10.0_f32).sqrt());
+        let period = 2.0 * std::f32::consts::PI * (1000.0 / gm).sqrt();
+        let mut worst = 0.0_f32;
+        for _ in 0..(2.0 * period) as usize {
+            ship.fly(&mut body, &Controls::default(), &settings, &[(Vec3::zeros(), gm)], 1.0);
+            worst = worst.max((body.translation.norm() - 10.0).abs());
+        }
+        assert!(worst < 0.1, "{worst}");
+        // Nothing pulls in arcade mode, the ship only slows down
+        ship.toggle_mode();
+        let before = ship.velocity;
+        ship.fly(&mut body, &Controls::default(), &settings, &[(Vec3::zeros(), gm)], 1.0);
+        assert!((ship.velocity - before * (1.0 - settings.damping)).norm() < 1e-6);
+    }
+}
@@ -9,29 +9,47 @@ pub struct Fragment {
     pub position: Vec2,
     pub depth: f32,
     pub normal: Vec3,
-    pub texture_pos: Vec2
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
+    pub texture_pos: Vec2,
+    // Change of texture_pos one pixel to the right and one pixel down, used for mip selection
+    pub uv_dx: Vec2,
+    pub uv_dy: Vec2,
+    pub world_position: Vec3,
 }
 
-impl Fragment {
-    pub fn new(x: f32, y: f32, depth: f32, normal:Vec3, texture_pos: Vec2) -> Self {
-        Fragment {
-            position: Vec2::new(x, y),
-            depth,
-            normal,
-            texture_pos,
-        }
-    }
-}
-
-pub fn triangle_fill(v1: &Vertex, v2:&Vertex ,v3:&Vertex)-> Vec<Fragment>{
-    let mut fragments = Vec::new();
+// Rasterizes a screen-space triangle, restricted to the pixel rectangle
+// (min_x, min_y, max_x, max_y), handing every covered fragment to `emit`
+pub fn triangle_fill(
+    v1: &Vertex,
+    v2: &Vertex,
+    v3: &Vertex,
+    clip_rect: (i32, i32, i32, i32),
+    perspective_correct: bool,
+    mut emit: impl FnMut(Fragment),
+) {
     let (a,b,c) = (v1.transformed_position,v2.transformed_position, v3.transformed_position);
 
     let (min_x, min_y, max_x, max_y) = calculate_bounding_box(&a, &b, &c);
+    // Never walk pixels outside of the given rectangle
+    let (min_x, min_y) = (min_x.max(clip_rect.0), min_y.max(clip_rect.1));
+    let (max_x, max_y) = (max_x.min(clip_rect.2), max_y.min(clip_rect.3));
     let t1 = v1.tex_coords;
     let t2 = v2.tex_coords;
     let t3 = v3.tex_coords;
     let triangle_area = edge_function(&a,&b,&c);
+    let weights_at = |point: &Vec3| {
+        let (w1, w2, w3) = barycentric_coordinates(point, &a, &b, &c, triangle_area);
+        if perspective_correct {
+            perspective_weights(w1, w2, w3, v1.inv_w, v2.inv_w, v3.inv_w)
+        } else {
+            (w1, w2, w3)
+        }
+    };
+    let uv_at = |point: &Vec3| {
+        let (p1, p2, p3) = weights_at(point);
+        t1 * p1 + t2 * p2 + t3 * p3
+    };
     // Iterate over each pixel in the bounding box
     for y in min_y..max_y{
         for x in min_x..max_x{
@@ -39,28 +57,45 @@ pub fn triangle_fill(v1: &Vertex, v2:&Vertex ,v3:&Vertex)-> Vec<Fragment>{
             
             let (w1, w2, w3) = barycentric_coordinates(&point, &a, &b, &c, triangle_area);
 
-            let u = t1.x * w1 + t2.x * w2 + t3.x * w3;
-            let v = t1.y * w1 + t2.y * w2 + t3.y * w3;
-
             // if w1!=0.0 || w2!=0.0 || w3!=0.0{
-                if w1>=0.0 && w1 <=1.0 &&
-                w2>=0.0 && w2 <=1.0 &&
-                w3>=0.0 && w3 <=1.0 {
+                if (0.0..=1.0).contains(&w1) &&
+                (0.0..=1.0).contains(&w2) &&
+                (0.0..=1.0).contains(&w3) {
+                    // Screen-space depth is linear, only attributes need the 1/w correction
                     let depth = a.z*w1 +b.z*w2 + c.z*w3;
-                    let old_normal = v1.transformed_normal*w1+v2.transformed_normal *w2 + v3.transformed_normal*w3;
+                    let (p1, p2, p3) = weights_at(&point);
+
+                    let texture_pos = t1 * p1 + t2 * p2 + t3 * p3;
+                    // Evaluated past the edges too, the plane equations extend beyond the triangle
+                    let uv_dx = uv_at(&Vec3::new(point.x + 1.0, point.y, 0.0)) - texture_pos;
+                    let uv_dy = uv_at(&Vec3::new(point.x, point.y + 1.0, 0.0)) - texture_pos;
+                    let old_normal = v1.transformed_normal*p1+v2.transformed_normal *p2 + v3.transformed_normal*p3;
                     let normal = old_normal.normalize();
-                    fragments.push(
-                        Fragment::new(
-                            x as f32, 
-                            y as f32, 
-                            depth, 
-                            normal, 
-                            Vec2::new(u,v)
-                        )
+                    let tangent = v1.transformed_tangent*p1 + v2.transformed_tangent*p2 + v3.transformed_tangent*p3;
+                    let bitangent = v1.transformed_bitangent*p1 + v2.transformed_bitangent*p2 + v3.transformed_bitangent*p3;
+                    let world_position = v1.world_position*p1 + v2.world_position*p2 + v3.world_position*p3;
+                    emit(
+                        Fragment {
+                            position: Vec2::new(x as f32, y as f32),
+                            depth,
+                            normal,
+                            tangent,
+                            bitangent,
+                            texture_pos,
+                            uv_dx,
+                            uv_dy,
+                            world_position,
+                        }
                     );
                 }
             // } 
         }
     }
-    fragments
+}
+
+// Rescales screen-space barycentrics so attributes are interpolated linearly in view space
+fn perspective_weights(w1: f32, w2: f32, w3: f32, inv_w1: f32, inv_w2: f32, inv_w3: f32) -> (f32, f32, f32) {
+    let (p1, p2, p3) = (w1 * inv_w1, w2 * inv_w2, w3 * inv_w3);
+    let sum = p1 + p2 + p3;
+    (p1 / sum, p2 / sum, p3 / sum)
 }
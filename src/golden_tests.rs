@@ -0,0 +1,151 @@
+// Golden-image regression tests: fixed scenes are rendered into a Framebuffer
+// and compared against the reference PNGs in tests/golden.
+//
+// Run with UPDATE_GOLDEN=1 to rewrite the references after an intended change.
+// On failure the rendered frame and a diff image are written to target/golden.
+
+use std::path::{Path, PathBuf};
+
+use nalgebra_glm::Vec3;
+
+use crate::camera::Camera;
+use crate::loaders::scene_file::{load_scene, SceneDescription, DEFAULT_SCENE};
+use crate::scene::{create_perspective_matrix, Scene, WINDOW_STRETCH};
+use crate::screen::framebuffer::Framebuffer;
+use crate::uniforms::RenderOptions;
+
+const WIDTH: usize = 320;
+const HEIGHT: usize = 240;
+// Largest per-channel difference for a pixel to still count as matching
+const CHANNEL_TOLERANCE: u8 = 8;
+// Fraction of pixels allowed to exceed the tolerance, absorbs edge pixels
+// that flip with floating point differences between platforms
+const MAX_MISMATCH_RATIO: f32 = 0.002;
+
+fn solar_system() -> SceneDescription {
+    load_scene(DEFAULT_SCENE).unwrap()
+}
+
+// The default scene reduced to the given bodies
+fn bodies(ids: &[&str]) -> SceneDescription {
+    let mut description = solar_system();
+    description.bodies.retain(|body| ids.contains(&body.id.as_str()));
+    description
+}
+
+// Where the scene file puts a body before the first tick
+fn position(description: &SceneDescription, id: &str) -> Vec3 {
+    description.bodies.iter().find(|body| body.id == id).unwrap().translation
+}
+
+// Camera position near `target`, `offset` is measured with x pointing away
+// from the sun and y up, so the lit side stays in view wherever the orbit is
+fn eye_near(target: Vec3, offset: Vec3) -> Vec3 {
+    let outwards = target.normalize();
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    let side = outwards.cross(&up).normalize();
+    target + outwards * offset.x + up * offset.y + side * offset.z
+}
+
+fn render(description: SceneDescription, ticks: u32, eye: Vec3, center: Vec3) -> Framebuffer {
+    let projection = create_perspective_matrix(WIDTH as f32, HEIGHT as f32 * WINDOW_STRETCH);
+    let mut scene = Scene::new(&description, WIDTH, HEIGHT, projection);
+    for _ in 0..ticks {
+        scene.tick();
+    }
+    let camera = Camera::new(eye, center, Vec3::new(0.0, 1.0, 0.0));
+    let mut framebuffer = Framebuffer::new(WIDTH, HEIGHT);
+    framebuffer.clear();
+    scene.render(&mut framebuffer, &camera, RenderOptions::default());
+    framebuffer
+}
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+fn output_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("target").join("golden")
+}
+
+fn assert_matches_golden(name: &str, framebuffer: &Framebuffer) {
+    let reference_path = golden_dir().join(format!("{name}.png"));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden_dir()).unwrap();
+        framebuffer.save_png(reference_path.to_str().unwrap()).unwrap();
+        return;
+    }
+
+    let reference = image::open(&reference_path)
+        .unwrap_or_else(|err| {
+            panic!("missing reference {}: {err}, run with UPDATE_GOLDEN=1", reference_path.display())
+        })
+        .to_rgb8();
+    assert_eq!(
+        (reference.width() as usize, reference.height() as usize),
+        (framebuffer.width, framebuffer.height),
+        "{name}: reference has a different size"
+    );
+
+    let mut diff = image::RgbImage::new(reference.width(), reference.height());
+    let mut mismatches = 0;
+    for (i, color) in framebuffer.buffer.iter().enumerate() {
+        let (x, y) = ((i % framebuffer.width) as u32, (i / framebuffer.width) as u32);
+        let expected = reference.get_pixel(x, y);
+        let largest = [color.r, color.g, color.b]
+            .iter()
+            .zip(expected.0.iter())
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap();
+        if largest > CHANNEL_TOLERANCE {
+            mismatches += 1;
+            diff.put_pixel(x, y, image::Rgb([255, 0, 0]));
+        } else {
+            // Dimmed reference so the mismatches stand out
+            let gray = ((expected[0] as u32 + expected[1] as u32 + expected[2] as u32) / 9) as u8;
+            diff.put_pixel(x, y, image::Rgb([gray, gray, gray]));
+        }
+    }
+
+    let ratio = mismatches as f32 / framebuffer.buffer.len() as f32;
+    if ratio > MAX_MISMATCH_RATIO {
+        std::fs::create_dir_all(output_dir()).unwrap();
+        let actual_path = output_dir().join(format!("{name}_actual.png"));
+        let diff_path = output_dir().join(format!("{name}_diff.png"));
+        framebuffer.save_png(actual_path.to_str().unwrap()).unwrap();
+        diff.save(&diff_path).unwrap();
+        panic!(
+            "{name}: {mismatches} pixels ({:.2}%) differ from the reference, see {}",
+            ratio * 100.0,
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+fn textured_sphere() {
+    let description = bodies(&["earth"]);
+    let earth = position(&description, "earth");
+    let framebuffer = render(description, 0, eye_near(earth, Vec3::new(-1.3, 0.6, 1.0)), earth);
+    assert_matches_golden("textured_sphere", &framebuffer);
+}
+
+#[test]
+fn saturn_rings() {
+    let description = bodies(&["saturn", "rings"]);
+    let saturn = position(&description, "saturn");
+    let framebuffer = render(description, 0, eye_near(saturn, Vec3::new(-2.8, 1.2, 1.4)), saturn);
+    assert_matches_golden("saturn_rings", &framebuffer);
+}
+
+#[test]
+fn full_system() {
+    let framebuffer = render(
+        solar_system(),
+        250,
+        Vec3::new(40.0, 40.0, 0.0),
+        Vec3::new(0.0, 0.0, 0.0),
+    );
+    assert_matches_golden("full_system", &framebuffer);
+}
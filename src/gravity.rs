@@ -0,0 +1,284 @@
+use std::fmt;
+
+use nalgebra_glm::{DVec3, Vec3};
+
+// How the optional N-body mode is run, the [gravity] table of the scene file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GravitySettings {
+    pub enabled: bool,
+    // In scene units: distance³ / (mass · tick²)
+    pub gravitational_constant: f64,
+    // Integration steps per tick, each one a fixed 1 / substeps of a tick
+    pub substeps: u32,
+}
+
+impl Default for GravitySettings {
+    fn default() -> Self {
+        GravitySettings {
+            enabled: false,
+            gravitational_constant: 1.0,
+            substeps: 20,
+        }
+    }
+}
+
+// State of one simulated body. Massless ones feel gravity without pulling on the rest.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Particle {
+    pub mass: f64,
+    pub position: DVec3,
+    pub velocity: DVec3,
+}
+
+// Change of the conserved quantities since the simulation started
+#[derive(Clone, Copy, Debug)]
+pub struct Drift {
+    // Relative to the starting total energy
+    pub energy: f64,
+    // Relative to the sum of the bodies' momentum magnitudes at the start
+    pub momentum: f64,
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "energy drift {:.3e}, momentum drift {:.3e}", self.energy, self.momentum)
+    }
+}
+
+// Substeps run by one call to NBody::advance
+const MAX_STEPS: u64 = 100_000;
+
+// Bodies moving under their mutual gravity, advanced with the kick-drift-kick
+// leapfrog (velocity Verlet). It is symplectic, so the energy error stays
+// bounded over long runs instead of growing, and pairwise forces keep the
+// total momentum exact up to rounding.
+pub struct NBody {
+    particles: Vec<Particle>,
+    accelerations: Vec<DVec3>,
+    gravitational_constant: f64,
+    substeps: u32,
+    initial_energy: f64,
+    initial_momentum: DVec3,
+    momentum_scale: f64,
+    // Part of a substep asked for but not run yet, in ticks
+    pending: f64,
+}
+
+impl NBody {
+    pub fn new(particles: Vec<Particle>, settings: &GravitySettings) -> Self {
+        let mut simulation = NBody {
+            accelerations: vec![DVec3::zeros(); particles.len()],
+            particles,
+            gravitational_constant: settings.gravitational_constant,
+            substeps: settings.substeps.max(1),
+            initial_energy: 0.0,
+            initial_momentum: DVec3::zeros(),
+            momentum_scale: 0.0,
+            pending: 0.0,
+        };
+        simulation.update_accelerations();
+        simulation.initial_energy = simulation.energy();
+        simulation.initial_momentum = simulation.momentum();
+        simulation.momentum_scale = simulation
+            .particles
+            .iter()
+            .map(|particle| particle.mass * particle.velocity.norm())
+            .sum();
+        simulation
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn position(&self, index: usize) -> Vec3 {
+        nalgebra_glm::convert(self.particles[index].position)
+    }
+
+    // Advances the bodies by `ticks`, backwards when negative, in whole
+    // substeps. The leapfrog is time reversible, so running back retraces the
+    // path. At most MAX_STEPS substeps are run per call so a large warp does
+    // not stall the frame, the rest stays pending for the following calls.
+    pub fn advance(&mut self, ticks: f64) {
+        let dt = 1.0 / self.substeps as f64;
+        self.pending += ticks;
+        // The epsilon keeps 1.0 from coming out as 19.999… substeps of 1/20
+        let steps = ((self.pending.abs() / dt + 1e-9).floor() as u64).min(MAX_STEPS);
+        let direction = self.pending.signum();
+        self.pending -= direction * steps as f64 * dt;
+        for _ in 0..steps {
+            self.step(direction * dt);
+        }
+    }
+
+    fn step(&mut self, dt: f64) {
+        for (particle, acceleration) in self.particles.iter_mut().zip(&self.accelerations) {
+            particle.velocity += acceleration * (dt / 2.0);
+            particle.position += particle.velocity * dt;
+        }
+        self.update_accelerations();
+        for (particle, acceleration) in self.particles.iter_mut().zip(&self.accelerations) {
+            particle.velocity += acceleration * (dt / 2.0);
+        }
+    }
+
+    fn update_accelerations(&mut self) {
+        self.accelerations.fill(DVec3::zeros());
+        for i in 0..self.particles.len() {
+            for j in i + 1..self.particles.len() {
+                let offset = self.particles[j].position - self.particles[i].position;
+                let distance_squared = offset.norm_squared();
+                // Bodies on top of each other would fling each other away
+                if distance_squared == 0.0 {
+                    continue;
+                }
+                let pull = offset * (self.gravitational_constant / (distance_squared * distance_squared.sqrt()));
+                self.accelerations[i] += pull * self.particles[j].mass;
+                self.accelerations[j] -= pull * self.particles[i].mass;
+            }
+        }
+    }
+
+    // Kinetic plus gravitational potential energy
+    pub fn energy(&self) -> f64 {
+        let mut energy = 0.0;
+        for (i, particle) in self.particles.iter().enumerate() {
+            energy += 0.5 * particle.mass * particle.velocity.norm_squared();
+            for other in &self.particles[i + 1..] {
+                let distance = (other.position - particle.position).norm();
+                if distance > 0.0 {
+                    energy -= self.gravitational_constant * particle.mass * other.mass / distance;
+                }
+            }
+        }
+        energy
+    }
+
+    pub fn momentum(&self) -> DVec3 {
+        self.particles
+            .iter()
+            .map(|particle| particle.velocity * particle.mass)
+            .sum()
+    }
+
+    pub fn drift(&self) -> Drift {
+        let relative = |change: f64, scale: f64| if scale == 0.0 { change } else { change / scale };
+        Drift {
+            energy: relative((self.energy() - self.initial_energy).abs(), self.initial_energy.abs()),
+            momentum: relative((self.momentum() - self.initial_momentum).norm(), self.momentum_scale),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn settings(substeps: u32) -> GravitySettings {
+        GravitySettings {
+            enabled: true,
+            gravitational_constant: 1.0,
+            substeps,
+        }
+    }
+
+    // A planet on a circular orbit around a star 100 times heavier
+    fn star_and_planet() -> Vec<Particle> {
+        let (star, planet, radius): (f64, f64, f64) = (1.0, 0.01, 4.0);
+        let speed = ((star + planet) / radius).sqrt();
+        // Both move around the common center of mass, which stays at rest
+        vec![
+            Particle {
+                mass: star,
+                position: DVec3::new(-radius * planet / (star + planet), 0.0, 0.0),
+                velocity: DVec3::new(0.0, 0.0, -speed * planet / (star + planet)),
+            },
+            Particle {
+                mass: planet,
+                position: DVec3::new(radius * star / (star + planet), 0.0, 0.0),
+                velocity: DVec3::new(0.0, 0.0, speed * star / (star + planet)),
+            },
+        ]
+    }
+
+    #[test]
+    fn circular_orbits_stay_circular_for_many_periods() {
+        let mut simulation = NBody::new(star_and_planet(), &settings(20));
+        let period = 2.0 * PI * (4.0_f64.powi(3) / 1.01).sqrt();
+        for _ in 0..(50.0 * period) as usize {
+            simulation.advance(1.0);
+            let [star, planet] = simulation.particles() else { unreachable!() };
+            assert!(((planet.position - star.position).norm() - 4.0).abs() < 1e-3);
+        }
+        let drift = simulation.drift();
+        assert!(drift.energy < 1e-6, "{drift:?}");
+        assert!(drift.momentum < 1e-12, "{drift:?}");
+    }
+
+    #[test]
+    fn energy_error_stays_bounded_on_an_eccentric_orbit() {
+        let mut particles = star_and_planet();
+        particles[1].velocity *= 0.7;
+        let mut simulation = NBody::new(particles, &settings(20));
+        let mut worst_early = 0.0_f64;
+        for _ in 0..500 {
+            simulation.advance(1.0);
+            worst_early = worst_early.max(simulation.drift().energy);
+        }
+        // Many orbits later the error is no larger than in the first few
+        for _ in 0..20_000 {
+            simulation.advance(1.0);
+            assert!(simulation.drift().energy < 2.0 * worst_early + 1e-9);
+        }
+    }
+
+    #[test]
+    fn running_backwards_retraces_the_orbit() {
+        let mut simulation = NBody::new(star_and_planet(), &settings(20));
+        let start = simulation.particles().to_vec();
+        // Uneven steps that do not line up with the substeps
+        for _ in 0..300 {
+            simulation.advance(0.37);
+        }
+        for _ in 0..111 {
+            simulation.advance(-1.0);
+        }
+        for (particle, start) in simulation.particles().iter().zip(&start) {
+            assert!((particle.position - start.position).norm() < 1e-9);
+            assert!((particle.velocity - start.velocity).norm() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn substeps_over_the_limit_are_run_by_the_following_calls() {
+        let mut at_once = NBody::new(star_and_planet(), &settings(20));
+        let mut in_parts = NBody::new(star_and_planet(), &settings(20));
+        in_parts.advance(3000.0);
+        in_parts.advance(3000.0);
+        // 120 000 substeps, more than one call runs
+        at_once.advance(6000.0);
+        assert_ne!(at_once.particles(), in_parts.particles());
+        at_once.advance(0.0);
+        assert_eq!(at_once.particles(), in_parts.particles());
+    }
+
+    #[test]
+    fn massless_bodies_do_not_pull() {
+        let mut particles = star_and_planet();
+        particles.push(Particle {
+            mass: 0.0,
+            position: DVec3::new(0.0, 20.0, 0.0),
+            velocity: DVec3::zeros(),
+        });
+        let mut with_probe = NBody::new(particles, &settings(4));
+        let mut without = NBody::new(star_and_planet(), &settings(4));
+        for _ in 0..10 {
+            with_probe.advance(1.0);
+            without.advance(1.0);
+        }
+        assert_eq!(with_probe.particles()[..2], without.particles()[..]);
+        // The probe falls towards the star
+        assert!(with_probe.particles()[2].position.y < 20.0);
+    }
+}
@@ -0,0 +1,104 @@
+use std::path::Path;
+
+use nalgebra_glm::Vec3;
+
+use crate::camera::Camera;
+use crate::cli::HeadlessOptions;
+use crate::hud;
+use crate::loaders::scene_file::load_scene;
+use crate::scene::{create_perspective_matrix, Scene, WINDOW_STRETCH};
+use crate::screen::framebuffer::Framebuffer;
+use crate::uniforms::RenderOptions;
+
+pub fn run(options: &HeadlessOptions) -> Result<(), String> {
+    // Same projection the window would use for this framebuffer size
+    let projection = create_perspective_matrix(
+        options.width as f32,
+        options.height as f32 * WINDOW_STRETCH,
+    );
+    let mut description = load_scene(&options.scene).map_err(|err| err.to_string())?;
+    description.gravity.enabled |= options.gravity;
+    let mut scene = Scene::new(&description, options.width, options.height, projection);
+    if let Some(report) = scene.assets().fallback_report() {
+        eprintln!("{report}");
+    }
+    if let Some(date) = options.date {
+        scene.set_date(date);
+    }
+    let mut framebuffer = Framebuffer::new(options.width, options.height);
+
+    for _ in 0..options.ticks {
+        scene.tick();
+    }
+    if let Some(drift) = scene.gravity_drift() {
+        println!("Gravity at tick {}: {drift}", scene.time());
+    }
+
+    let mut camera = Camera::new(
+        Vec3::new(33.0, 1.5, 0.0),
+        Vec3::new(30.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    );
+    let focus_index = match &options.focus {
+        Some(id) => Some(scene.find(id).ok_or_else(|| format!("no body with id '{id}'"))?),
+        None => None,
+    };
+
+    for frame in 0..options.frames {
+        if frame > 0 {
+            scene.tick();
+        }
+        if let Some(index) = focus_index {
+            camera.focus_on(&scene.uniform_array[index].celestial_body);
+        } else if options.birds_eye {
+            camera.birds_eye();
+        }
+        if let Some(eye) = options.eye {
+            camera.eye = eye;
+        }
+        if let Some(center) = options.center {
+            camera.center = center;
+        }
+
+        framebuffer.clear();
+        scene.render(&mut framebuffer, &camera, RenderOptions::default());
+        if let Some(julian_date) = scene.julian_date() {
+            hud::draw_date(&mut framebuffer, julian_date);
+        }
+
+        let path = frame_path(&options.output, frame, options.frames);
+        framebuffer
+            .save_png(&path)
+            .map_err(|err| format!("failed to write {path}: {err}"))?;
+        println!("Wrote {path}");
+    }
+    Ok(())
+}
+
+// The number goes before the extension of the file name, dots in the
+// directories leading up to it are left alone
+fn frame_path(output: &str, frame: u32, frames: u32) -> String {
+    if frames == 1 {
+        return output.to_string();
+    }
+    let path = Path::new(output);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{stem}_{frame:04}.{}", extension.to_string_lossy()),
+        None => format!("{stem}_{frame:04}"),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_numbers_go_before_the_extension() {
+        assert_eq!(frame_path("frame.png", 0, 1), "frame.png");
+        assert_eq!(frame_path("frame.png", 3, 10), "frame_0003.png");
+        assert_eq!(frame_path("./frames/out", 2, 3), "./frames/out_0002");
+        assert_eq!(frame_path("../renders.v2/out.png", 12, 20), "../renders.v2/out_0012.png");
+    }
+}
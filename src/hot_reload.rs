@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::loaders::scene_file::load_scene;
+use crate::scene::Scene;
+
+// How often the watched files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Remembers the modification time of a set of files
+struct FileWatcher {
+    modified: HashMap<String, Option<SystemTime>>,
+}
+
+impl FileWatcher {
+    fn new() -> Self {
+        FileWatcher {
+            modified: HashMap::new(),
+        }
+    }
+
+    // Starts watching the paths that are not watched yet
+    fn watch(&mut self, paths: impl IntoIterator<Item = String>) {
+        for path in paths {
+            self.modified
+                .entry(path)
+                .or_insert_with_key(|path| modified_time(path));
+        }
+    }
+
+    // Files written, deleted or created since the last call
+    fn changed(&mut self) -> Vec<String> {
+        let mut changed = Vec::new();
+        for (path, modified) in self.modified.iter_mut() {
+            let current = modified_time(path);
+            if current != *modified {
+                *modified = current;
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+// Polls the scene file and every mesh and image it uses, and swaps changed
+// ones into the running scene. Files that fail to load are reported and the
+// previous version is kept.
+pub struct HotReload {
+    scene_path: String,
+    watcher: FileWatcher,
+    last_poll: Instant,
+}
+
+impl HotReload {
+    pub fn new(scene_path: &str, scene: &Scene) -> Self {
+        let mut watcher = FileWatcher::new();
+        watcher.watch([scene_path.to_string()]);
+        watcher.watch(scene.asset_paths());
+        HotReload {
+            scene_path: scene_path.to_string(),
+            watcher,
+            last_poll: Instant::now(),
+        }
+    }
+
+    // Returns true when the scene file was reloaded, bodies may have been
+    // added, removed or reordered
+    pub fn poll(&mut self, scene: &mut Scene) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+
+        let mut changed = self.watcher.changed();
+        // The scene goes first so the other files are reloaded for its current bodies
+        changed.sort_by_key(|path| *path != self.scene_path);
+
+        let mut scene_reloaded = false;
+        for path in changed {
+            let result = if path == self.scene_path {
+                load_scene(&path)
+                    .map(|description| scene.reload(&description))
+                    .map_err(|err| err.to_string())
+                    .inspect(|_| scene_reloaded = true)
+            } else {
+                scene.reload_file(&path).map_err(|err| err.to_string())
+            };
+            match result {
+                Ok(()) => println!("Reloaded {path}"),
+                Err(err) => eprintln!("{err}, keeping the previous version"),
+            }
+        }
+
+        if scene_reloaded {
+            self.watcher.watch(scene.asset_paths());
+        }
+        scene_reloaded
+    }
+}
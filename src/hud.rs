@@ -0,0 +1,29 @@
+use crate::calendar::CalendarDate;
+use crate::screen::framebuffer::Framebuffer;
+use crate::screen::text::{draw_text, text_width, GLYPH_HEIGHT};
+
+// Text drawn over the rendered frame, in framebuffer pixels
+const MARGIN: usize = 10;
+const TEXT_SCALE: usize = 2;
+const TEXT_COLOR: u32 = 0xffffff;
+
+// Calendar date and Julian date of the ephemeris clock, top left
+pub fn draw_date(framebuffer: &mut Framebuffer, julian_date: f64) {
+    let text = format!("{} UTC  JD {julian_date:.2}", CalendarDate::from_julian_date(julian_date));
+    framebuffer.set_current_color(TEXT_COLOR);
+    draw_text(framebuffer, &text, MARGIN, MARGIN, TEXT_SCALE);
+}
+
+// Time warp indicator of the simulation clock, top right
+pub fn draw_warp(framebuffer: &mut Framebuffer, label: &str) {
+    let x = framebuffer.width.saturating_sub(MARGIN + text_width(label, TEXT_SCALE));
+    framebuffer.set_current_color(TEXT_COLOR);
+    draw_text(framebuffer, label, x, MARGIN, TEXT_SCALE);
+}
+
+// Something that just happened to the ship, bottom left
+pub fn draw_event(framebuffer: &mut Framebuffer, text: &str) {
+    let y = framebuffer.height.saturating_sub(MARGIN + GLYPH_HEIGHT * TEXT_SCALE);
+    framebuffer.set_current_color(TEXT_COLOR);
+    draw_text(framebuffer, text, MARGIN, y, TEXT_SCALE);
+}
@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::Arc;
+
+use image::RgbaImage;
+
+use crate::loaders::error::LoadError;
+use crate::loaders::gltf;
+use crate::loaders::normal_map::NormalMap;
+use crate::loaders::obj::{Obj, ObjOptions};
+use crate::loaders::texture::Texture;
+use crate::mesh::Mesh;
+use crate::model::Model;
+use crate::rasterizer::BlendMode;
+use crate::screen::color::Color;
+
+// Typed index into an AssetManager, only valid for the manager that issued it
+// (or clones of it)
+pub struct Handle<T> {
+    index: usize,
+    asset: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    fn new(index: usize) -> Self {
+        Handle {
+            index,
+            asset: PhantomData,
+        }
+    }
+}
+
+// Derives would require T: Clone/Debug/PartialEq, which the handle never needs
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Handle({})", self.index)
+    }
+}
+
+// Assets of one kind, each loaded once per path
+struct Store<T> {
+    items: Vec<Arc<T>>,
+    paths: HashMap<String, usize>,
+}
+
+impl<T> Store<T> {
+    fn new() -> Self {
+        Store {
+            items: Vec::new(),
+            paths: HashMap::new(),
+        }
+    }
+
+    fn get(&self, handle: Handle<T>) -> &T {
+        &self.items[handle.index]
+    }
+
+    fn find(&self, path: &str) -> Option<Handle<T>> {
+        self.paths.get(path).map(|&index| Handle::new(index))
+    }
+
+    // Stores `item` under `path`, replacing what was loaded from it before
+    fn insert(&mut self, path: &str, item: T) -> Handle<T> {
+        match self.find(path) {
+            Some(handle) => {
+                self.items[handle.index] = Arc::new(item);
+                handle
+            }
+            None => {
+                self.items.push(Arc::new(item));
+                let index = self.items.len() - 1;
+                self.paths.insert(path.to_string(), index);
+                Handle::new(index)
+            }
+        }
+    }
+
+    fn load<E>(&mut self, path: &str, load: impl FnOnce(&str) -> Result<T, E>) -> Result<Handle<T>, E> {
+        if let Some(handle) = self.find(path) {
+            return Ok(handle);
+        }
+        let item = load(path)?;
+        Ok(self.insert(path, item))
+    }
+
+    // Swaps the asset behind an existing handle, unknown paths are ignored
+    fn reload<E>(&mut self, path: &str, load: impl FnOnce(&str) -> Result<T, E>) -> Result<(), E> {
+        if let Some(handle) = self.find(path) {
+            self.items[handle.index] = Arc::new(load(path)?);
+        }
+        Ok(())
+    }
+}
+
+// Cloning only copies the Arcs, the asset data itself is shared
+impl<T> Clone for Store<T> {
+    fn clone(&self) -> Self {
+        Store {
+            items: self.items.clone(),
+            paths: self.paths.clone(),
+        }
+    }
+}
+
+// How one part of a body's model is shaded. Maps and values from the part's
+// material win over the ones the body declares.
+#[derive(Clone, Debug)]
+pub struct Surface {
+    pub texture: Option<Handle<Texture>>,
+    pub normal_map: Option<Handle<NormalMap>>,
+    pub diffuse: Color,
+    pub shininess: f32,
+    pub specular_strength: f32,
+    pub blend_mode: BlendMode,
+}
+
+// The handles a body is drawn with, one surface per model part
+#[derive(Clone, Debug)]
+pub struct BodyHandles {
+    pub model: Handle<Model>,
+    pub surfaces: Vec<Surface>,
+}
+
+// An asset that could not be loaded and was replaced by a generated one
+#[derive(Clone, Debug)]
+pub struct Fallback {
+    pub kind: &'static str,
+    pub path: String,
+    // Bodies drawn with the placeholder
+    pub bodies: Vec<String>,
+    pub error: String,
+}
+
+// Owns every model, texture and normal map of the application. Lookups are
+// plain indexing, so the shader can read it from every rasterizer thread
+// without locking. Changes are made on a clone that then replaces the shared
+// manager (see Scene::reload).
+//
+// Files that are missing or corrupt are replaced by a sphere, a checkerboard
+// texture or a flat normal map under the same path, so fixing the file and
+// reloading it swaps the real asset in.
+//
+// Images embedded in a model are loaded through the same texture and normal
+// map stores, under the key the model gives them.
+#[derive(Clone)]
+pub struct AssetManager {
+    models: Store<Model>,
+    textures: Store<Texture>,
+    normal_maps: Store<NormalMap>,
+    // What each OBJ file was loaded with, reloads use the same
+    model_options: HashMap<String, ObjOptions>,
+    fallbacks: Vec<Fallback>,
+}
+
+impl Default for AssetManager {
+    fn default() -> Self {
+        AssetManager::new()
+    }
+}
+
+impl AssetManager {
+    pub fn new() -> Self {
+        AssetManager {
+            models: Store::new(),
+            textures: Store::new(),
+            normal_maps: Store::new(),
+            model_options: HashMap::new(),
+            fallbacks: Vec::new(),
+        }
+    }
+
+    // The load_*_or_fallback functions never fail, `body` names who asked
+    // for the file in the warning and the fallback report
+    pub fn load_model_or_fallback(&mut self, path: &str, options: ObjOptions, body: &str) -> Handle<Model> {
+        // An edited scene can ask for a file already loaded to be completed differently
+        let previous = self.model_options.insert(path.to_string(), options);
+        if previous.is_some_and(|previous| previous != options) {
+            if let Err(err) = self.models.reload(path, |path| load_model(path, options)) {
+                eprintln!("warning: body '{body}': {err}, keeping the previous mesh");
+            }
+        }
+        let load = |path: &str| load_model(path, options);
+        let placeholder = || Model::from_mesh("placeholder", Mesh::uv_sphere(32, 16));
+        load_or_fallback(&mut self.models, &mut self.fallbacks, "mesh", path, body, load, placeholder)
+    }
+
+    pub fn load_texture_or_fallback(&mut self, path: &str, body: &str) -> Handle<Texture> {
+        let load = |path: &str| load_texture(&self.models, path);
+        let store = &mut self.textures;
+        load_or_fallback(store, &mut self.fallbacks, "texture", path, body, load, Texture::checkerboard)
+    }
+
+    pub fn load_normal_map_or_fallback(&mut self, path: &str, body: &str) -> Handle<NormalMap> {
+        let load = |path: &str| load_normal_map(&self.models, path);
+        let store = &mut self.normal_maps;
+        load_or_fallback(store, &mut self.fallbacks, "normal map", path, body, load, NormalMap::flat)
+    }
+
+    // Summary of every placeholder in use, None when everything loaded
+    pub fn fallback_report(&self) -> Option<String> {
+        if self.fallbacks.is_empty() {
+            return None;
+        }
+        let mut report = format!("{} asset(s) replaced by placeholders:", self.fallbacks.len());
+        for fallback in &self.fallbacks {
+            report.push_str(&format!(
+                "\n  {} {} (used by {}): {}",
+                fallback.kind,
+                fallback.path,
+                fallback.bodies.join(", "),
+                fallback.error
+            ));
+        }
+        Some(report)
+    }
+
+    pub fn model(&self, handle: Handle<Model>) -> &Model {
+        self.models.get(handle)
+    }
+
+    pub fn texture(&self, handle: Handle<Texture>) -> &Texture {
+        self.textures.get(handle)
+    }
+
+    pub fn normal_map(&self, handle: Handle<NormalMap>) -> &NormalMap {
+        self.normal_maps.get(handle)
+    }
+
+    // Loads every asset read from `path` again, handles stay valid. Placeholders
+    // for the path are replaced by the real asset once it loads.
+    pub fn reload(&mut self, path: &str) -> Result<(), LoadError> {
+        let options = self.model_options.get(path).copied().unwrap_or_default();
+        self.models.reload(path, |path| load_model(path, options))?;
+        // Images embedded in a reloaded model are replaced along with it
+        let mut paths = vec![path.to_string()];
+        if let Some(model) = self.models.find(path) {
+            paths.extend(self.models.get(model).images.iter().map(|image| image.key.clone()));
+        }
+        for path in &paths {
+            self.textures.reload(path, |path| load_texture(&self.models, path))?;
+            self.normal_maps.reload(path, |path| load_normal_map(&self.models, path))?;
+            self.fallbacks.retain(|fallback| &fallback.path != path);
+        }
+        Ok(())
+    }
+
+    // Every file loaded so far, placeholders included. Embedded images are
+    // left out, they change with the model file.
+    pub fn paths(&self) -> Vec<String> {
+        let models = self.models.paths.keys();
+        let textures = self.textures.paths.keys();
+        let normal_maps = self.normal_maps.paths.keys();
+        models
+            .chain(textures)
+            .chain(normal_maps)
+            .filter(|path| embedded_image(&self.models, path).is_none())
+            .cloned()
+            .collect()
+    }
+}
+
+// Loads `path` into the store, or warns and stores the placeholder under it
+fn load_or_fallback<T>(
+    store: &mut Store<T>,
+    fallbacks: &mut Vec<Fallback>,
+    kind: &'static str,
+    path: &str,
+    body: &str,
+    load: impl FnOnce(&str) -> Result<T, LoadError>,
+    placeholder: impl FnOnce() -> T,
+) -> Handle<T> {
+    let warn = |error: &str| eprintln!("warning: body '{body}': {error}, using a placeholder {kind}");
+
+    // Already replaced for another body, only the report changes
+    if let Some(fallback) = fallbacks
+        .iter_mut()
+        .find(|fallback| fallback.kind == kind && fallback.path == path)
+    {
+        warn(&fallback.error);
+        if !fallback.bodies.iter().any(|other| other == body) {
+            fallback.bodies.push(body.to_string());
+        }
+        return store.find(path).expect("placeholders are stored under their path");
+    }
+
+    match store.load(path, load) {
+        Ok(handle) => handle,
+        Err(err) => {
+            warn(&err.to_string());
+            fallbacks.push(Fallback {
+                kind,
+                path: path.to_string(),
+                bodies: vec![body.to_string()],
+                error: err.to_string(),
+            });
+            store.insert(path, placeholder())
+        }
+    }
+}
+
+// glTF files are picked by extension, everything else is read as OBJ
+fn load_model(path: &str, options: ObjOptions) -> Result<Model, LoadError> {
+    let extension = Path::new(path).extension().and_then(|extension| extension.to_str());
+    match extension.map(str::to_ascii_lowercase).as_deref() {
+        Some("gltf" | "glb") => gltf::load(path),
+        _ => Obj::load(path, options).map(|obj| obj.get_model()),
+    }
+}
+
+fn embedded_image<'a>(models: &'a Store<Model>, key: &str) -> Option<&'a RgbaImage> {
+    models.items.iter().find_map(|model| model.image(key))
+}
+
+fn load_texture(models: &Store<Model>, path: &str) -> Result<Texture, LoadError> {
+    match embedded_image(models, path) {
+        Some(image) => Ok(Texture::from_image(image)),
+        None => Texture::new(path),
+    }
+}
+
+fn load_normal_map(models: &Store<Model>, path: &str) -> Result<NormalMap, LoadError> {
+    match embedded_image(models, path) {
+        Some(image) => Ok(NormalMap::from_image(image)),
+        None => NormalMap::new(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_are_loaded_once_and_reloaded_behind_the_same_handle() {
+        let path = "./assets/normal_maps/moon_np.jpg";
+        let mut assets = AssetManager::new();
+        let first = assets.load_normal_map_or_fallback(path, "sun");
+        let second = assets.load_normal_map_or_fallback(path, "moon");
+        assert_eq!(first, second);
+
+        let shared = Arc::new(assets.clone());
+        assets.reload(path).unwrap();
+        assert_eq!(assets.load_normal_map_or_fallback(path, "moon"), first);
+        // The shared copy keeps reading the map it was cloned with
+        assert!(!std::ptr::eq(assets.normal_map(first), shared.normal_map(first)));
+        assert!(assets.fallback_report().is_none());
+    }
+
+    #[test]
+    fn missing_files_fall_back_and_are_reported() {
+        let mut assets = AssetManager::new();
+        let texture = assets.load_texture_or_fallback("./assets/textures/missing.jpg", "mars");
+        assets.load_texture_or_fallback("./assets/textures/missing.jpg", "venus");
+        assets.load_model_or_fallback("./assets/3d_models/missing.obj", ObjOptions::default(), "mars");
+
+        let filter = crate::loaders::mipmap::FilterMode::Nearest;
+        let magenta = assets.texture(texture).sample(0.01, 0.99, 0.0, filter);
+        let black = assets.texture(texture).sample(0.2, 0.99, 0.0, filter);
+        assert_eq!((magenta.to_hex(), black.to_hex()), (0xff00ff, 0x000000));
+
+        let report = assets.fallback_report().unwrap();
+        assert!(report.starts_with("2 asset(s)"), "{report}");
+        assert!(report.contains("texture ./assets/textures/missing.jpg (used by mars, venus)"), "{report}");
+        assert!(report.contains("mesh ./assets/3d_models/missing.obj (used by mars)"), "{report}");
+    }
+}
@@ -0,0 +1,33 @@
+use std::fmt;
+
+// Why an asset file could not be turned into a texture, normal map or mesh
+#[derive(Debug)]
+pub enum LoadError {
+    Image { path: String, source: image::ImageError },
+    Obj { path: String, source: tobj::LoadError },
+    Gltf { path: String, source: gltf::Error },
+    // The OBJ or glTF file parsed but cannot be rendered
+    InvalidMesh { path: String, message: String },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Image { path, source } => write!(f, "failed to load image {path}: {source}"),
+            LoadError::Obj { path, source } => write!(f, "failed to load mesh {path}: {source}"),
+            LoadError::Gltf { path, source } => write!(f, "failed to load model {path}: {source}"),
+            LoadError::InvalidMesh { path, message } => write!(f, "invalid mesh {path}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Image { source, .. } => Some(source),
+            LoadError::Obj { source, .. } => Some(source),
+            LoadError::Gltf { source, .. } => Some(source),
+            LoadError::InvalidMesh { .. } => None,
+        }
+    }
+}
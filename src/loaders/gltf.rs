@@ -0,0 +1,332 @@
+use std::path::Path;
+
+use ::gltf::buffer;
+use ::gltf::image::{Data, Format, Source};
+use ::gltf::material::AlphaMode;
+use ::gltf::mesh::Mode;
+use ::gltf::Node;
+use image::RgbaImage;
+use nalgebra_glm::{mat4_to_mat3, Mat3, Mat4, Vec2, Vec3, Vec4};
+use crate::loaders::error::LoadError;
+use crate::model::{EmbeddedImage, Material, Model, ModelPart};
+use crate::{mesh::Mesh, screen::color::Color, vertex::Vertex};
+
+// What every node of the file needs while the scene is walked
+struct Context<'a> {
+    path: &'a str,
+    buffers: &'a [buffer::Data],
+    // Asset key of every image in the document, by image index
+    image_keys: &'a [String],
+}
+
+// Reads a .gltf or .glb file. External buffers and images are resolved next to it.
+pub fn load(path: &str) -> Result<Model, LoadError> {
+    let bytes = std::fs::read(path).map_err(|err| gltf_error(path, ::gltf::Error::Io(err)))?;
+    parse(&bytes, path)
+}
+
+// `path` names the file in errors and is the base of relative URIs
+pub fn parse(bytes: &[u8], path: &str) -> Result<Model, LoadError> {
+    let ::gltf::Gltf { document, blob } =
+        ::gltf::Gltf::from_slice(bytes).map_err(|err| gltf_error(path, err))?;
+    let base = Path::new(path).parent().unwrap_or(Path::new(""));
+    let buffers = ::gltf::import_buffers(&document, Some(base), blob)
+        .map_err(|err| gltf_error(path, err))?;
+
+    // Image files stay separate assets shared with the rest of the scene,
+    // images inside the buffers or data URIs travel with the model
+    let mut image_keys = Vec::new();
+    let mut images = Vec::new();
+    for image in document.images() {
+        match image.source() {
+            Source::Uri { uri, .. } if !uri.starts_with("data:") => {
+                image_keys.push(base.join(uri).to_string_lossy().into_owned());
+            }
+            source => {
+                let key = format!("{path}#image{}", image.index());
+                let data = Data::from_source(source, Some(base), &buffers)
+                    .map_err(|err| gltf_error(path, err))?;
+                let image = to_rgba(&data).ok_or_else(|| {
+                    invalid(path, format!("image {} has fewer pixels than its size", image.index()))
+                })?;
+                images.push(EmbeddedImage { key: key.clone(), image });
+                image_keys.push(key);
+            }
+        }
+    }
+
+    let context = Context { path, buffers: &buffers, image_keys: &image_keys };
+    let mut parts = Vec::new();
+    // Files without a default scene are drawn with their first one
+    if let Some(scene) = document.default_scene().or_else(|| document.scenes().next()) {
+        for node in scene.nodes() {
+            read_node(&node, &Mat4::identity(), &context, &mut parts)?;
+        }
+    }
+    if parts.is_empty() {
+        return Err(invalid(path, "contains no triangles".to_string()));
+    }
+
+    Ok(Model { parts, images })
+}
+
+// Adds the triangles of `node` and its children, baked into model space
+fn read_node(
+    node: &Node,
+    parent: &Mat4,
+    context: &Context,
+    parts: &mut Vec<ModelPart>,
+) -> Result<(), LoadError> {
+    let transform = parent * Mat4::from(node.transform().matrix());
+
+    if let Some(mesh) = node.mesh() {
+        let name = mesh.name().or(node.name())
+            .map_or_else(|| format!("mesh {}", mesh.index()), str::to_string);
+        for primitive in mesh.primitives() {
+            // Points and lines have nothing to draw, strips and fans are not exported by our tools
+            if primitive.mode() != Mode::Triangles {
+                continue;
+            }
+            let mesh = read_primitive(&primitive, &transform, context, &name)?;
+            parts.push(ModelPart {
+                name: name.clone(),
+                mesh,
+                material: convert_material(&primitive.material(), context.image_keys),
+            });
+        }
+    }
+
+    for child in node.children() {
+        read_node(&child, &transform, context, parts)?;
+    }
+    Ok(())
+}
+
+fn read_primitive(
+    primitive: &::gltf::Primitive,
+    transform: &Mat4,
+    context: &Context,
+    name: &str,
+) -> Result<Mesh, LoadError> {
+    let invalid = |message: &str| invalid(context.path, format!("mesh '{name}' {message}"));
+    let reader = primitive.reader(|buffer| context.buffers.get(buffer.index()).map(|data| &data[..]));
+
+    let positions: Vec<[f32; 3]> = reader.read_positions()
+        .ok_or_else(|| invalid("has no positions"))?
+        .collect();
+    let normals: Vec<[f32; 3]> = reader.read_normals()
+        .ok_or_else(|| invalid("needs a normal for every vertex"))?
+        .collect();
+    let texcoords: Vec<[f32; 2]> = reader.read_tex_coords(0)
+        .ok_or_else(|| invalid("needs texture coordinates for every vertex"))?
+        .into_f32()
+        .collect();
+    let mut indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+    if normals.len() != positions.len() || texcoords.len() != positions.len() {
+        return Err(invalid("has attributes of different lengths"));
+    }
+    if !indices.len().is_multiple_of(3) || indices.iter().any(|&index| index as usize >= positions.len()) {
+        return Err(invalid("has indices that do not form triangles of its vertices"));
+    }
+
+    // Normals follow the inverse transpose so non-uniform scales keep them perpendicular
+    let linear: Mat3 = mat4_to_mat3(transform);
+    let normal_matrix = linear.try_inverse().map_or(linear, |inverse| inverse.transpose());
+    // Mirroring transforms turn the triangles inside out
+    if linear.determinant() < 0.0 {
+        for triangle in indices.chunks_mut(3) {
+            triangle.swap(1, 2);
+        }
+    }
+
+    let vertex_color = Color::from_hex(0x5797ff);
+    let vertices = (0..positions.len())
+        .map(|i| {
+            let [x, y, z] = positions[i];
+            let [u, v] = texcoords[i];
+            Vertex {
+                color: vertex_color,
+                position: (transform * Vec4::new(x, y, z, 1.0)).xyz(),
+                normal: (normal_matrix * Vec3::from(normals[i])).normalize(),
+                // glTF puts the origin of the texture at the top left, the samplers at the bottom left
+                tex_coords: Vec2::new(u, 1.0 - v),
+                ..Default::default()
+            }
+        })
+        .collect();
+    let mut mesh = Mesh { vertices, indices };
+    mesh.compute_tangents();
+    Ok(mesh)
+}
+
+fn convert_material(material: &::gltf::Material, image_keys: &[String]) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    let [r, g, b, a] = pbr.base_color_factor();
+    // Masked materials would need an alpha test, they are drawn opaque
+    let alpha = if material.alpha_mode() == AlphaMode::Blend { a } else { 1.0 };
+    let to_channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as i32;
+    let image_key = |texture: ::gltf::Texture| image_keys[texture.source().index()].clone();
+    Material {
+        diffuse: Color::new_rgba(to_channel(r), to_channel(g), to_channel(b), to_channel(alpha)),
+        // Metalness and roughness have no Blinn-Phong equivalent, the body's highlight is used
+        specular_strength: None,
+        shininess: None,
+        diffuse_map: pbr.base_color_texture().map(|info| image_key(info.texture())),
+        normal_map: material.normal_texture().map(|info| image_key(info.texture())),
+    }
+}
+
+// Decoded images come in whatever layout the file had, the textures want RGBA8
+fn to_rgba(data: &Data) -> Option<RgbaImage> {
+    // Channels per pixel, bytes per channel
+    let (channels, size) = match data.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+    let pixels = data.pixels
+        .chunks_exact(channels * size)
+        .flat_map(|pixel| {
+            let channel = |i: usize| {
+                let bytes = &pixel[i * size..(i + 1) * size];
+                match size {
+                    1 => bytes[0],
+                    2 => (u16::from_ne_bytes([bytes[0], bytes[1]]) >> 8) as u8,
+                    _ => {
+                        let value = f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                        (value.clamp(0.0, 1.0) * 255.0).round() as u8
+                    }
+                }
+            };
+            // One and two channel images are gray, with alpha in the second channel
+            match channels {
+                1 => [channel(0), channel(0), channel(0), 255],
+                2 => [channel(0), channel(0), channel(0), channel(1)],
+                3 => [channel(0), channel(1), channel(2), 255],
+                _ => [channel(0), channel(1), channel(2), channel(3)],
+            }
+        })
+        .collect();
+    RgbaImage::from_raw(data.width, data.height, pixels)
+}
+
+fn gltf_error(path: &str, source: ::gltf::Error) -> LoadError {
+    LoadError::Gltf { path: path.to_string(), source }
+}
+
+fn invalid(path: &str, message: String) -> LoadError {
+    LoadError::InvalidMesh { path: path.to_string(), message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loaders::assets::AssetManager;
+
+    // One triangle moved 5 units along z by its node, with a half transparent
+    // material that uses an embedded 1x1 red image and an external normal map
+    const TRIANGLE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "name": "Root", "children": [1] }, { "mesh": 0, "translation": [0, 0, 5] }],
+        "meshes": [{
+            "name": "Hull",
+            "primitives": [{
+                "attributes": { "POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2 },
+                "indices": 3,
+                "material": 0
+            }]
+        }],
+        "materials": [{
+            "pbrMetallicRoughness": { "baseColorFactor": [1, 0.5, 0.5, 0.5], "baseColorTexture": { "index": 0 } },
+            "normalTexture": { "index": 1 },
+            "alphaMode": "BLEND"
+        }],
+        "textures": [{ "source": 0 }, { "source": 1 }],
+        "images": [
+            { "uri": "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR4nGP4z8DwHwAFAAH/iZk9HQAAAABJRU5ErkJggg==" },
+            { "uri": "hull_normal.png" }
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] },
+            { "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC3" },
+            { "bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC2" },
+            { "bufferView": 3, "componentType": 5123, "count": 3, "type": "SCALAR" }
+        ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 72, "byteLength": 24 },
+            { "buffer": 0, "byteOffset": 96, "byteLength": 6 }
+        ],
+        "buffers": [{
+            "byteLength": 104,
+            "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAAAAAAAAAAAAIA/AAAAAAAAAAAAAIA/AAAAAAAAAAAAAIA/AAAAAAAAAAAAAIA/AAAAAAAAAAAAAIA/AAABAAIAAAA="
+        }]
+    }"#;
+
+    #[test]
+    fn nodes_meshes_and_materials_are_read() {
+        let model = parse(TRIANGLE.as_bytes(), "models/ship.gltf").unwrap();
+        assert_eq!(model.parts.len(), 1);
+
+        let hull = &model.parts[0];
+        assert_eq!(hull.name, "Hull");
+        assert_eq!(hull.mesh.indices, vec![0, 1, 2]);
+        assert_eq!(hull.mesh.vertices[1].position, Vec3::new(1.0, 0.0, 5.0));
+        assert_eq!(hull.mesh.vertices[0].tex_coords, Vec2::new(0.0, 1.0));
+        assert_eq!(hull.material.diffuse.to_hex(), 0xff8080);
+        assert_eq!(hull.material.diffuse.a, 128);
+        assert_eq!(hull.material.diffuse_map.as_deref(), Some("models/ship.gltf#image0"));
+        assert_eq!(hull.material.normal_map.as_deref(), Some("models/hull_normal.png"));
+
+        let image = model.image("models/ship.gltf#image0").unwrap();
+        assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn mirroring_nodes_flip_the_winding() {
+        let mirrored = TRIANGLE.replace(r#""translation": [0, 0, 5]"#, r#""scale": [-1, 1, 1]"#);
+        let model = parse(mirrored.as_bytes(), "ship.gltf").unwrap();
+        let mesh = &model.parts[0].mesh;
+        assert_eq!(mesh.indices, vec![0, 2, 1]);
+        assert_eq!(mesh.vertices[1].position, Vec3::new(-1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn broken_files_are_errors() {
+        assert!(matches!(parse(b"{ not json", "ship.gltf"), Err(LoadError::Gltf { .. })));
+        let no_scene = TRIANGLE.replace(r#""scene": 0,"#, "").replace(r#""scenes": [{ "nodes": [0] }],"#, "");
+        assert!(matches!(parse(no_scene.as_bytes(), "ship.gltf"), Err(LoadError::InvalidMesh { .. })));
+    }
+
+    #[test]
+    fn embedded_images_load_as_textures() {
+        let path = std::env::temp_dir().join(format!("embedded_images_{}.gltf", std::process::id()));
+        std::fs::write(&path, TRIANGLE).unwrap();
+        let path = path.to_string_lossy().into_owned();
+
+        let mut assets = AssetManager::new();
+        let model = assets.load_model_or_fallback(&path, Default::default(), "ship");
+        let key = assets.model(model).parts[0].material.diffuse_map.clone().unwrap();
+        let texture = assets.load_texture_or_fallback(&key, "ship");
+        let filter = crate::loaders::mipmap::FilterMode::Nearest;
+        assert_eq!(assets.texture(texture).sample(0.5, 0.5, 0.0, filter).to_hex(), 0xff0000);
+        assert!(assets.fallback_report().is_none());
+        // Embedded images are not files the hot reload could watch
+        assert_eq!(assets.paths(), vec![path.clone()]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
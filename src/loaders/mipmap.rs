@@ -0,0 +1,136 @@
+use nalgebra_glm::{Vec2, Vec3};
+use crate::screen::color::Color;
+
+// Texture filtering used when sampling textures and normal maps
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterMode {
+    // Closest texel of the full resolution image
+    Nearest,
+    // Bilinear filtering inside the closest mip level
+    Bilinear,
+    // Bilinear filtering blended between the two closest mip levels
+    Trilinear,
+}
+
+// Anything that can be stored in a mip chain and filtered
+pub trait Texel: Copy {
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+impl Texel for Color {
+    fn lerp(self, other: Color, t: f32) -> Color {
+        Color::lerp(self, other, t)
+    }
+}
+
+impl Texel for Vec3 {
+    fn lerp(self, other: Vec3, t: f32) -> Vec3 {
+        self + (other - self) * t
+    }
+}
+
+#[derive(Clone, Debug)]
+struct MipLevel<T> {
+    width: u32,
+    height: u32,
+    data: Vec<T>,
+}
+
+impl<T: Texel> MipLevel<T> {
+    // Texel lookup with repeat wrapping, v = 0 is the bottom row of the image
+    fn texel(&self, x: i64, y: i64) -> T {
+        let x = x.rem_euclid(self.width as i64) as u32;
+        let y = y.rem_euclid(self.height as i64) as u32;
+        let index = ((self.height - y - 1) * self.width + x) as usize;
+        self.data[index]
+    }
+
+    fn nearest(&self, u: f32, v: f32) -> T {
+        let x = (u * self.width as f32).floor() as i64;
+        let y = (v * self.height as f32).floor() as i64;
+        self.texel(x, y)
+    }
+
+    fn bilinear(&self, u: f32, v: f32) -> T {
+        let x = u * self.width as f32 - 0.5;
+        let y = v * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let bottom = self.texel(x0, y0).lerp(self.texel(x0 + 1, y0), tx);
+        let top = self.texel(x0, y0 + 1).lerp(self.texel(x0 + 1, y0 + 1), tx);
+        bottom.lerp(top, ty)
+    }
+
+    // 2x2 box filter, odd sizes reuse the last row/column
+    fn downsample(&self) -> MipLevel<T> {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut data = Vec::with_capacity((width * height) as usize);
+        for row in 0..height {
+            for x in 0..width {
+                let at = |dx: u32, dy: u32| {
+                    let sx = (x * 2 + dx).min(self.width - 1);
+                    let sy = (row * 2 + dy).min(self.height - 1);
+                    self.data[(sy * self.width + sx) as usize]
+                };
+                let top = at(0, 0).lerp(at(1, 0), 0.5);
+                let bottom = at(0, 1).lerp(at(1, 1), 0.5);
+                data.push(top.lerp(bottom, 0.5));
+            }
+        }
+        MipLevel { width, height, data }
+    }
+}
+
+// Full resolution image followed by every halved level down to 1x1
+#[derive(Clone, Debug)]
+pub struct MipChain<T> {
+    levels: Vec<MipLevel<T>>,
+}
+
+impl<T: Texel> MipChain<T> {
+    pub fn new(width: u32, height: u32, data: Vec<T>) -> Self {
+        let mut levels = vec![MipLevel { width, height, data }];
+        while let Some(last) = levels.last() {
+            if last.width == 1 && last.height == 1 {
+                break;
+            }
+            let next = last.downsample();
+            levels.push(next);
+        }
+        MipChain { levels }
+    }
+
+    // Mip level from the screen-space UV derivatives of the fragment
+    pub fn level_of_detail(&self, uv_dx: Vec2, uv_dy: Vec2) -> f32 {
+        let base = &self.levels[0];
+        let size = Vec2::new(base.width as f32, base.height as f32);
+        let dx = uv_dx.component_mul(&size).magnitude();
+        let dy = uv_dy.component_mul(&size).magnitude();
+        let footprint = dx.max(dy);
+        if footprint.is_finite() && footprint > 1.0 {
+            footprint.log2().min((self.levels.len() - 1) as f32)
+        } else {
+            0.0
+        }
+    }
+
+    pub fn sample(&self, u: f32, v: f32, lod: f32, filter: FilterMode) -> T {
+        match filter {
+            FilterMode::Nearest => self.levels[0].nearest(u, v),
+            FilterMode::Bilinear => {
+                let level = (lod.round() as usize).min(self.levels.len() - 1);
+                self.levels[level].bilinear(u, v)
+            }
+            FilterMode::Trilinear => {
+                let lower = (lod.floor() as usize).min(self.levels.len() - 1);
+                let upper = (lower + 1).min(self.levels.len() - 1);
+                let fine = self.levels[lower].bilinear(u, v);
+                let coarse = self.levels[upper].bilinear(u, v);
+                fine.lerp(coarse, lod.fract())
+            }
+        }
+    }
+}
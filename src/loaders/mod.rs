@@ -1,3 +1,8 @@
+pub mod assets;
+pub mod error;
+pub mod gltf;
+pub mod mipmap;
 pub mod normal_map;
 pub mod obj;
+pub mod scene_file;
 pub mod texture;
\ No newline at end of file
@@ -1,21 +1,24 @@
-use nalgebra_glm::Vec3;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use once_cell::sync::Lazy;
+use image::RgbaImage;
+use nalgebra_glm::{Vec2, Vec3};
+use crate::loaders::error::LoadError;
+use crate::loaders::mipmap::{FilterMode, MipChain};
 
-static NORMAL_MAPS: Lazy<Mutex<HashMap<String, Arc<NormalMap>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
 #[derive(Clone, Debug)]
 pub struct NormalMap {
-    width: u32,
-    height: u32,
-    data: Vec<Vec3>,
+    mips: MipChain<Vec3>,
 }
 
 impl NormalMap {
-    pub fn new(path: &str) -> Result<Self, image::ImageError> {
-        let img = image::open(path)?.to_rgba8();
+    pub fn new(path: &str) -> Result<Self, LoadError> {
+        let img = image::open(path)
+            .map_err(|source| LoadError::Image { path: path.to_string(), source })?
+            .to_rgba8();
+        Ok(NormalMap::from_image(&img))
+    }
+
+    pub fn from_image(img: &RgbaImage) -> Self {
         let (width, height) = img.dimensions();
-        
+
         // Convert RGB colors to normal vectors (assuming tangent-space normal map)
         let data = img.pixels()
             .map(|p| {
@@ -27,31 +30,20 @@ impl NormalMap {
             })
             .collect();
 
-        Ok(NormalMap { width, height, data })
+        NormalMap { mips: MipChain::new(width, height, data) }
     }
 
-    pub fn sample(&self, u: f32, v: f32) -> Vec3 {
-        let u = u.fract().abs();
-        let v = v.fract().abs();
-        
-        let x = (u * (self.width as f32)) as u32;
-        let y = (v * (self.height as f32)) as u32;
-        
-        let index = ((self.height-y-1) * self.width + x) as usize;
-        self.data[index]
+    // Every texel points straight out of the surface, leaves the vertex normals untouched
+    pub fn flat() -> Self {
+        NormalMap { mips: MipChain::new(1, 1, vec![Vec3::new(0.0, 0.0, 1.0)]) }
     }
-}
 
-pub fn init_normal_map(id: &str,path: &str) -> Result<(), image::ImageError> {
-    let normal_map = NormalMap::new(path)?;
+    pub fn level_of_detail(&self, uv_dx: Vec2, uv_dy: Vec2) -> f32 {
+        self.mips.level_of_detail(uv_dx, uv_dy)
+    }
 
-    let mut normal_maps = NORMAL_MAPS.lock().unwrap();
-    normal_maps.insert(id.to_string(), Arc::new(normal_map));
-    Ok(())
+    // Filtered samples are not unit length, callers renormalize
+    pub fn sample(&self, u: f32, v: f32, lod: f32, filter: FilterMode) -> Vec3 {
+        self.mips.sample(u, v, lod, filter)
+    }
 }
-
-pub fn with_normal_map(id: &str, f: impl FnOnce(&NormalMap) -> Vec3) -> Vec3 {
-    let normal_maps = NORMAL_MAPS.lock().unwrap();
-    let normal_map = normal_maps.get(id).expect("Normal map not initialized");
-    f(&normal_map)
-}
\ No newline at end of file
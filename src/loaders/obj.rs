@@ -1,61 +1,391 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor};
+use std::path::Path;
+
 use tobj;
 use nalgebra_glm::{Vec2, Vec3};
+use crate::loaders::error::LoadError;
+use crate::model::{Material, Model, ModelPart};
+use crate::mesh::{Mesh, NormalGeneration, UvProjection};
 use crate::{screen::color::Color, vertex::Vertex};
 
-pub struct Obj {
+// How the normals and texture coordinates a file leaves out are made up
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ObjOptions {
+    pub normals: NormalGeneration,
+    pub uv_projection: UvProjection,
+}
+
+// One `o`/`g` block of the file, split further wherever the material changes
+pub struct ObjObject {
+    pub name: String,
     pub vertices: Vec<Vec3>,
+    // Empty when the file has no `vn` lines
     pub normals: Vec<Vec3>,
+    // Empty when the file has no `vt` lines
     pub texcoords: Vec<Vec2>,
     pub indices: Vec<u32>,
+    pub material_id: Option<usize>,
+}
+
+pub struct Obj {
+    pub objects: Vec<ObjObject>,
+    pub materials: Vec<Material>,
+    pub options: ObjOptions,
 }
 
 impl Obj {
-    pub fn load(filename: &str) -> Result<Self, tobj::LoadError> {
-        let (models, _) = tobj::load_obj(filename, &tobj::LoadOptions {
+    pub fn load(filename: &str, options: ObjOptions) -> Result<Self, LoadError> {
+        let file = File::open(filename).map_err(|_| LoadError::Obj {
+            path: filename.to_string(),
+            source: tobj::LoadError::OpenFileFailed,
+        })?;
+        let base = Path::new(filename).parent().unwrap_or(Path::new(""));
+        Obj::parse(&mut BufReader::new(file), filename, options, |mtl| tobj::load_mtl(base.join(mtl)))
+    }
+
+    // `filename` names the file in errors, map paths are resolved next to it
+    pub fn parse<B, ML>(
+        reader: &mut B,
+        filename: &str,
+        options: ObjOptions,
+        material_loader: ML,
+    ) -> Result<Self, LoadError>
+    where
+        B: BufRead,
+        ML: Fn(&Path) -> tobj::MTLLoadResult,
+    {
+        let mut source = String::new();
+        reader.read_to_string(&mut source).map_err(|_| LoadError::Obj {
+            path: filename.to_string(),
+            source: tobj::LoadError::ReadError,
+        })?;
+        check_faces(&source).map_err(|message| LoadError::InvalidMesh {
+            path: filename.to_string(),
+            message,
+        })?;
+
+        let (models, materials) = tobj::load_obj_buf(&mut Cursor::new(source), &tobj::LoadOptions {
             single_index: true,
             triangulate: true,
             ..Default::default()
-        })?;
+        }, material_loader)
+        .map_err(|source| LoadError::Obj { path: filename.to_string(), source })?;
 
-        let mesh = &models[0].mesh;
+        // A broken material library only costs the looks, the geometry is still usable
+        let materials = materials.unwrap_or_else(|err| {
+            eprintln!("warning: {filename}: failed to load materials: {err}, using the default material");
+            Vec::new()
+        });
+        let base = Path::new(filename).parent().unwrap_or(Path::new(""));
+        let materials = materials.iter().map(|material| convert_material(material, base)).collect();
 
-        let vertices: Vec<Vec3> = mesh.positions.chunks(3)
-            .map(|v| Vec3::new(v[0], v[1], v[2]))
-            .collect();
+        let mut objects = Vec::new();
+        for model in models {
+            let mesh = model.mesh;
+            // Objects made only of points or lines have nothing to draw
+            if mesh.indices.is_empty() {
+                continue;
+            }
 
-        let normals: Vec<Vec3> = mesh.normals.chunks(3)
-            .map(|n| Vec3::new(n[0], n[1], n[2]))
-            .collect();
+            objects.push(ObjObject {
+                name: model.name,
+                vertices: mesh.positions.chunks(3)
+                    .map(|v| Vec3::new(v[0], v[1], v[2]))
+                    .collect(),
+                normals: mesh.normals.chunks(3)
+                    .map(|n| Vec3::new(n[0], n[1], n[2]))
+                    .collect(),
+                texcoords: mesh.texcoords.chunks(2)
+                    .map(|t| Vec2::new(t[0], t[1]))
+                    .collect(),
+                indices: mesh.indices,
+                material_id: mesh.material_id,
+            });
+        }
+        if objects.is_empty() {
+            return Err(LoadError::InvalidMesh {
+                path: filename.to_string(),
+                message: "contains no faces".to_string(),
+            });
+        }
 
-        let texcoords: Vec<Vec2> = mesh.texcoords.chunks(2)
-            .map(|t| Vec2::new(t[0], t[1]))
-            .collect();
+        Ok(Obj { objects, materials, options })
+    }
 
-        let indices = mesh.indices.clone();
+    pub fn get_model(&self) -> Model {
+        let parts = self.objects.iter()
+            .map(|object| ModelPart {
+                name: object.name.clone(),
+                mesh: object.get_mesh(self.options),
+                material: object.material_id
+                    .and_then(|id| self.materials.get(id))
+                    .cloned()
+                    .unwrap_or_default(),
+            })
+            .collect();
+        Model { parts, images: Vec::new() }
+    }
+}
 
-        Ok(Obj {
+impl ObjObject {
+    pub fn get_mesh(&self, options: ObjOptions) -> Mesh {
+        let vertex_color = Color::from_hex(0x5797ff);
+        let vertices = (0..self.vertices.len())
+            .map(|i| Vertex {
+                color: vertex_color,
+                position: self.vertices[i],
+                normal: self.normals.get(i).copied().unwrap_or_default(),
+                tex_coords: self.texcoords.get(i).copied().unwrap_or_default(),
+                ..Default::default()
+            })
+            .collect();
+        let mut mesh = Mesh {
             vertices,
-            normals,
-            texcoords,
-            indices,
-        })
+            indices: self.indices.clone(),
+        };
+        if self.texcoords.is_empty() {
+            mesh.generate_uvs(options.uv_projection);
+        }
+        if self.normals.is_empty() {
+            mesh.generate_normals(options.normals);
+        }
+        mesh.compute_tangents();
+        mesh
     }
+}
 
-    pub fn get_vertex_array(&self)-> Vec<Vertex>{
-        let mut vertex_array = Vec::new();
-        let vertex_color = Color::from_hex(0x5797ff);
-        for i in &self.indices{
-            vertex_array.push(
-                Vertex{
-                    color: vertex_color,
-                    position: self.vertices[*i as usize],
-                    normal: self.normals[*i as usize],
-                    tex_coords: self.texcoords[*i as usize],
-                    transformed_normal: Vec3::new(0.0,0.0,0.0),
-                    transformed_position: Vec3::new(0.0,0.0,0.0),
+// tobj neither says where a bad index is nor rejects files where only some
+// faces have normals or texture coordinates, it fills the missing ones in
+// from unrelated vertices. Returns the first problem with its line number.
+fn check_faces(source: &str) -> Result<(), String> {
+    const KINDS: [&str; 3] = ["vertex", "texture coordinate", "normal"];
+    // Elements of each kind declared so far
+    let mut counts = [0usize; 3];
+    // Whether the first face of the current object or group had texture
+    // coordinates and normals, and its line. Each object is a mesh of its own
+    // and gets whatever it is missing generated separately.
+    let mut first: [Option<(bool, usize)>; 2] = [None, None];
+
+    for (line_number, line) in source.lines().enumerate().map(|(i, line)| (i + 1, line)) {
+        let mut words = line.split_whitespace();
+        let vertices = match words.next() {
+            Some("v") => { counts[0] += 1; continue; }
+            Some("vt") => { counts[1] += 1; continue; }
+            Some("vn") => { counts[2] += 1; continue; }
+            Some("o" | "g") => { first = [None, None]; continue; }
+            Some("f") => words,
+            _ => continue,
+        };
+
+        for vertex in vertices {
+            let fields: Vec<&str> = vertex.split('/').collect();
+            if fields.len() > 3 || fields[0].is_empty() {
+                return Err(format!("line {line_number}: '{vertex}' is not a face vertex"));
+            }
+            for (kind, field) in fields.iter().enumerate().filter(|(_, field)| !field.is_empty()) {
+                let index: i64 = field
+                    .parse()
+                    .map_err(|_| format!("line {line_number}: '{field}' is not a {} index", KINDS[kind]))?;
+                // Negative indices count back from the last element declared
+                let count = counts[kind];
+                if index == 0 || index.unsigned_abs() as usize > count {
+                    return Err(format!(
+                        "line {line_number}: face refers to {} {index} but only {count} are declared before it",
+                        KINDS[kind]
+                    ));
                 }
-            )
+            }
+
+            for (slot, first) in first.iter_mut().enumerate() {
+                let present = fields.get(slot + 1).is_some_and(|field| !field.is_empty());
+                match *first {
+                    None => *first = Some((present, line_number)),
+                    Some((expected, first_line)) if expected != present => {
+                        let (has, other) = if present { ("has a", "does not") } else { ("has no", "does") };
+                        return Err(format!(
+                            "line {line_number}: '{vertex}' {has} {} index but the face on line {first_line} {other}",
+                            KINDS[slot + 1]
+                        ));
+                    }
+                    Some(_) => {}
+                }
+            }
         }
-        vertex_array
     }
-}
\ No newline at end of file
+    Ok(())
+}
+
+fn convert_material(material: &tobj::Material, base: &Path) -> Material {
+    let resolve = |map: &String| base.join(map).to_string_lossy().into_owned();
+    let to_channel = |value: f32| (value * 255.0).round() as i32;
+    let diffuse = material.diffuse.unwrap_or([1.0, 1.0, 1.0]);
+    let alpha = material.dissolve.unwrap_or(1.0);
+    Material {
+        diffuse: Color::new_rgba(
+            to_channel(diffuse[0]),
+            to_channel(diffuse[1]),
+            to_channel(diffuse[2]),
+            to_channel(alpha),
+        ),
+        specular_strength: material.specular.map(|ks| (ks[0] + ks[1] + ks[2]) / 3.0),
+        shininess: material.shininess,
+        diffuse_map: material.diffuse_texture.as_ref().map(resolve),
+        // tobj reads map_Bump and bump, `norm` ends up with the unknown parameters
+        normal_map: material.normal_texture.as_ref()
+            .or_else(|| material.unknown_param.get("norm"))
+            .map(resolve),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_OBJECTS: &str = "
+mtllib ship.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+v 0 0 1
+vt 0 0
+vt 1 0
+vt 0 1
+vn 0 0 1
+o Hull
+usemtl metal
+f 1/1/1 2/2/1 3/3/1
+o Window
+usemtl glass
+f 1/1/1 3/3/1 4/2/1
+";
+
+    const MATERIALS: &str = "
+newmtl metal
+Kd 0.5 0.5 0.5
+Ks 0.9 0.9 0.9
+Ns 80
+map_Kd hull.png
+norm hull_normal.png
+
+newmtl glass
+Kd 0.2 0.4 1.0
+d 0.5
+";
+
+    fn materials(_: &Path) -> tobj::MTLLoadResult {
+        tobj::load_mtl_buf(&mut Cursor::new(MATERIALS))
+    }
+
+    #[test]
+    fn every_object_keeps_its_material() {
+        let obj = Obj::parse(&mut Cursor::new(TWO_OBJECTS), "models/ship.obj", ObjOptions::default(), materials).unwrap();
+        let model = obj.get_model();
+        assert_eq!(model.parts.len(), 2);
+
+        let hull = &model.parts[0];
+        assert_eq!(hull.name, "Hull");
+        assert_eq!(hull.mesh.indices.len(), 3);
+        assert_eq!(hull.material.diffuse.to_hex(), 0x808080);
+        assert_eq!(hull.material.shininess, Some(80.0));
+        assert_eq!(hull.material.diffuse_map.as_deref(), Some("models/hull.png"));
+        assert_eq!(hull.material.normal_map.as_deref(), Some("models/hull_normal.png"));
+
+        let window = &model.parts[1];
+        assert_eq!(window.name, "Window");
+        assert_eq!(window.material.diffuse.a, 128);
+        assert_eq!(window.material.diffuse_map, None);
+    }
+
+    #[test]
+    fn missing_material_library_uses_the_default_material() {
+        let missing = |_: &Path| -> tobj::MTLLoadResult { Err(tobj::LoadError::OpenFileFailed) };
+        let obj = Obj::parse(&mut Cursor::new(TWO_OBJECTS), "ship.obj", ObjOptions::default(), missing).unwrap();
+        let model = obj.get_model();
+        assert_eq!(model.parts.len(), 2);
+        assert_eq!(model.parts[0].material.diffuse.to_hex(), 0xffffff);
+    }
+
+    #[test]
+    fn files_without_faces_are_rejected() {
+        let result = parse("v 0 0 0\nv 1 0 0\n", ObjOptions::default());
+        assert!(matches!(result, Err(LoadError::InvalidMesh { .. })));
+    }
+
+    // A unit square in the XY plane, facing +z, without normals or texture coordinates
+    const BARE_SQUARE: &str = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+f 1 2 3 4
+";
+
+    fn parse(source: &str, options: ObjOptions) -> Result<Obj, LoadError> {
+        let no_materials = |_: &Path| -> tobj::MTLLoadResult { Ok((Vec::new(), Default::default())) };
+        Obj::parse(&mut Cursor::new(source), "test.obj", options, no_materials)
+    }
+
+    fn error_message(source: &str) -> String {
+        match parse(source, ObjOptions::default()) {
+            Err(LoadError::InvalidMesh { message, .. }) => message,
+            Err(other) => panic!("expected an invalid mesh, got {other}"),
+            Ok(_) => panic!("expected an invalid mesh"),
+        }
+    }
+
+    #[test]
+    fn missing_normals_and_uvs_are_generated() {
+        let options = ObjOptions { normals: NormalGeneration::Flat, uv_projection: UvProjection::Planar };
+        let mesh = parse(BARE_SQUARE, options).unwrap().get_model().parts.remove(0).mesh;
+        assert_eq!(mesh.indices.len(), 6);
+        for vertex in &mesh.vertices {
+            assert_eq!(vertex.normal, Vec3::new(0.0, 0.0, 1.0));
+            assert_eq!(vertex.tex_coords, vertex.position.xy());
+        }
+
+        // Smooth normals keep the four shared vertices
+        let mesh = parse(BARE_SQUARE, ObjOptions::default()).unwrap().get_model().parts.remove(0).mesh;
+        assert_eq!(mesh.vertices.len(), 4);
+        assert!(mesh.vertices.iter().all(|vertex| vertex.normal == Vec3::new(0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn given_normals_and_uvs_are_kept() {
+        let source = BARE_SQUARE.replace("f 1 2 3 4", "vt 0.25 0.75\nvn 0 1 0\nf 1/1/1 2/1/1 3/1/1");
+        let mesh = parse(&source, ObjOptions::default()).unwrap().get_model().parts.remove(0).mesh;
+        for vertex in &mesh.vertices {
+            assert_eq!(vertex.normal, Vec3::new(0.0, 1.0, 0.0));
+            assert_eq!(vertex.tex_coords, Vec2::new(0.25, 0.75));
+        }
+    }
+
+    #[test]
+    fn inconsistent_indices_are_reported_with_their_line() {
+        let out_of_range = BARE_SQUARE.replace("f 1 2 3 4", "f 1 2 9");
+        assert_eq!(
+            error_message(&out_of_range),
+            "line 6: face refers to vertex 9 but only 4 are declared before it"
+        );
+        let relative = BARE_SQUARE.replace("f 1 2 3 4", "f -1 -2 -5");
+        assert!(error_message(&relative).starts_with("line 6: face refers to vertex -5"));
+        let missing_normal = BARE_SQUARE.replace("f 1 2 3 4", "vn 0 0 1\nf 1//1 2//1 3//1\nf 1 3 4");
+        assert_eq!(
+            error_message(&missing_normal),
+            "line 8: '1' has no normal index but the face on line 7 does"
+        );
+        assert!(error_message(&BARE_SQUARE.replace("f 1 2 3 4", "f 1 2 x")).contains("'x' is not a vertex index"));
+    }
+
+    #[test]
+    fn objects_can_differ_in_what_they_give() {
+        let source = BARE_SQUARE.replace("f 1 2 3 4", "vt 0 0\no Textured\nf 1/1 2/1 3/1\no Bare\nf 1 3 4");
+        let model = parse(&source, ObjOptions::default()).unwrap().get_model();
+        assert_eq!(model.parts.len(), 2);
+        // The bare object gets its texture coordinates generated, not borrowed
+        assert!(model.parts[1].mesh.vertices.iter().any(|vertex| vertex.tex_coords != Vec2::zeros()));
+        // Within one object the faces still have to agree
+        let mixed = source.replace("f 1 3 4", "f 1 3 4\nf 1/1 2/1 4/1");
+        assert_eq!(error_message(&mixed), "line 11: '1/1' has a texture coordinate index but the face on line 10 does not");
+    }
+}
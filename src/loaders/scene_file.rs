@@ -0,0 +1,793 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::fmt;
+
+use nalgebra_glm::Vec3;
+use serde::Deserialize;
+
+use crate::celestial_bodies::CelestialBody;
+use crate::calendar::{CalendarDate, J2000};
+use crate::collision::{CollisionResponse, CollisionSettings};
+use crate::culling::CullMode;
+use crate::ephemeris::EphemerisSettings;
+use crate::flight::{FlightMode, FlightSettings};
+use crate::gravity::GravitySettings;
+use crate::loaders::obj::ObjOptions;
+use crate::mesh::{NormalGeneration, UvProjection};
+use crate::orbit::OrbitalElements;
+use crate::rasterizer::BlendMode;
+
+pub const DEFAULT_SCENE: &str = "./assets/scenes/solar_system.toml";
+
+// A parsed and validated scene file
+#[derive(Clone, Debug)]
+pub struct SceneDescription {
+    // Mesh id to the OBJ or glTF file
+    pub meshes: HashMap<String, MeshSource>,
+    // Bodies in file order, parents always come before their children
+    pub bodies: Vec<CelestialBody>,
+    pub gravity: GravitySettings,
+    pub ephemeris: EphemerisSettings,
+    pub flight: FlightSettings,
+    pub collisions: CollisionSettings,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshSource {
+    pub path: String,
+    // Only used by OBJ files, for what they leave out
+    pub options: ObjOptions,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io { path: String, source: std::io::Error },
+    Parse { path: String, message: String },
+    Invalid { body: String, field: &'static str, message: String },
+    InvalidMesh { mesh: String, field: &'static str, message: String },
+    // A field of one of the settings tables: [gravity], [ephemeris], [flight] or [collisions]
+    InvalidSetting { table: &'static str, field: &'static str, message: String },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => write!(f, "failed to read scene {path}: {source}"),
+            SceneError::Parse { path, message } => write!(f, "failed to parse scene {path}: {message}"),
+            SceneError::Invalid { body, field, message } => {
+                write!(f, "invalid scene: body '{body}', field '{field}': {message}")
+            }
+            SceneError::InvalidMesh { mesh, field, message } => {
+                write!(f, "invalid scene: mesh '{mesh}', field '{field}': {message}")
+            }
+            SceneError::InvalidSetting { table, field, message } => {
+                write!(f, "invalid scene: [{table}], field '{field}': {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+// File layout, every field is optional here so missing ones can be reported
+// with the body they belong to
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawScene {
+    #[serde(default)]
+    meshes: HashMap<String, RawMesh>,
+    #[serde(default)]
+    bodies: Vec<RawBody>,
+    gravity: Option<RawGravity>,
+    ephemeris: Option<RawEphemeris>,
+    flight: Option<RawFlight>,
+    collisions: Option<RawCollisions>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawGravity {
+    enabled: Option<bool>,
+    gravitational_constant: Option<f64>,
+    substeps: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawEphemeris {
+    enabled: Option<bool>,
+    date: Option<String>,
+    days_per_tick: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawFlight {
+    mode: Option<String>,
+    main_thrust: Option<f32>,
+    rcs_thrust: Option<f32>,
+    turn_acceleration: Option<f32>,
+    damping: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawCollisions {
+    enabled: Option<bool>,
+    response: Option<String>,
+    restitution: Option<f32>,
+}
+
+// A mesh is either just its path or a table with the path and options
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawMesh {
+    Path(String),
+    Table(RawMeshTable),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawMeshTable {
+    path: Option<String>,
+    normals: Option<String>,
+    uv_projection: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawBody {
+    id: Option<String>,
+    mesh: Option<String>,
+    texture: Option<String>,
+    normal_map: Option<String>,
+    scale: Option<f32>,
+    position: Option<[f32; 3]>,
+    rotation: Option<[f32; 3]>,
+    rotation_period: Option<f32>,
+    cull_mode: Option<String>,
+    blend_mode: Option<String>,
+    shininess: Option<f32>,
+    specular_strength: Option<f32>,
+    parent: Option<String>,
+    orbit: Option<RawOrbit>,
+    mass: Option<f32>,
+    velocity: Option<[f32; 3]>,
+    collides: Option<bool>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawOrbit {
+    // `radius` reads better for the circular orbits most bodies use
+    #[serde(alias = "radius")]
+    semi_major_axis: Option<f32>,
+    period: Option<f32>,
+    eccentricity: Option<f32>,
+    // Angles are in degrees
+    inclination: Option<f32>,
+    ascending_node: Option<f32>,
+    argument_of_periapsis: Option<f32>,
+    mean_anomaly: Option<f32>,
+}
+
+pub fn load_scene(path: &str) -> Result<SceneDescription, SceneError> {
+    let source = std::fs::read_to_string(path).map_err(|source| SceneError::Io {
+        path: path.to_string(),
+        source,
+    })?;
+    parse_scene(&source, path)
+}
+
+// `path` is only used to name the file in errors
+pub fn parse_scene(source: &str, path: &str) -> Result<SceneDescription, SceneError> {
+    let raw: RawScene = toml::from_str(source).map_err(|err| SceneError::Parse {
+        path: path.to_string(),
+        message: err.to_string(),
+    })?;
+
+    // Sorted so the same file always reports the same error first
+    let mut raw_meshes: Vec<(String, RawMesh)> = raw.meshes.into_iter().collect();
+    raw_meshes.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let mut meshes: HashMap<String, MeshSource> = HashMap::new();
+    for (id, raw_mesh) in raw_meshes {
+        let mesh = validate_mesh(&id, raw_mesh)?;
+        // Files are loaded once, so every mesh using one has to agree on how
+        if let Some((other, _)) = meshes
+            .iter()
+            .find(|(_, other)| other.path == mesh.path && other.options != mesh.options)
+        {
+            return Err(invalid_mesh(&id, "path", format!("is used by mesh '{other}' with different options")));
+        }
+        meshes.insert(id, mesh);
+    }
+
+    let mut bodies: Vec<CelestialBody> = Vec::with_capacity(raw.bodies.len());
+    for (index, raw_body) in raw.bodies.into_iter().enumerate() {
+        let body = validate_body(index, raw_body, &meshes, &bodies)?;
+        bodies.push(body);
+    }
+
+    let gravity = match raw.gravity {
+        Some(gravity) => validate_gravity(gravity)?,
+        None => GravitySettings::default(),
+    };
+
+    let ephemeris = match raw.ephemeris {
+        Some(ephemeris) => validate_ephemeris(ephemeris)?,
+        None => EphemerisSettings::default(),
+    };
+
+    let flight = match raw.flight {
+        Some(flight) => validate_flight(flight)?,
+        None => FlightSettings::default(),
+    };
+
+    let collisions = match raw.collisions {
+        Some(collisions) => validate_collisions(collisions)?,
+        None => CollisionSettings::default(),
+    };
+
+    Ok(SceneDescription { meshes, bodies, gravity, ephemeris, flight, collisions })
+}
+
+fn validate_gravity(raw: RawGravity) -> Result<GravitySettings, SceneError> {
+    let defaults = GravitySettings::default();
+    let gravitational_constant = finite(raw.gravitational_constant.unwrap_or(defaults.gravitational_constant))
+        .map_err(|message| invalid_setting("gravity", "gravitational_constant", message))?;
+    if gravitational_constant <= 0.0 {
+        return Err(invalid_setting("gravity", "gravitational_constant", "must be greater than zero"));
+    }
+    let substeps = raw.substeps.unwrap_or(defaults.substeps);
+    if substeps == 0 {
+        return Err(invalid_setting("gravity", "substeps", "must be at least 1"));
+    }
+    Ok(GravitySettings {
+        enabled: raw.enabled.unwrap_or(defaults.enabled),
+        gravitational_constant,
+        substeps,
+    })
+}
+
+fn validate_mesh(id: &str, raw: RawMesh) -> Result<MeshSource, SceneError> {
+    let table = match raw {
+        RawMesh::Path(path) => return Ok(MeshSource { path, options: ObjOptions::default() }),
+        RawMesh::Table(table) => table,
+    };
+    let path = table.path.ok_or_else(|| invalid_mesh(id, "path", "is required"))?;
+    let normals = match table.normals.as_deref() {
+        None | Some("smooth") => NormalGeneration::Smooth,
+        Some("flat") => NormalGeneration::Flat,
+        Some(other) => return Err(invalid_mesh(id, "normals", format!("'{other}' is not one of smooth, flat"))),
+    };
+    let uv_projection = match table.uv_projection.as_deref() {
+        None | Some("spherical") => UvProjection::Spherical,
+        Some("planar") => UvProjection::Planar,
+        Some(other) => {
+            return Err(invalid_mesh(id, "uv_projection", format!("'{other}' is not one of spherical, planar")))
+        }
+    };
+    Ok(MeshSource { path, options: ObjOptions { normals, uv_projection } })
+}
+
+fn validate_ephemeris(raw: RawEphemeris) -> Result<EphemerisSettings, SceneError> {
+    let defaults = EphemerisSettings::default();
+    let start = match raw.date {
+        Some(date) => CalendarDate::parse(&date)
+            .map_err(|message| invalid_setting("ephemeris", "date", message))?
+            .julian_date(),
+        None => J2000,
+    };
+    let days_per_tick = finite(raw.days_per_tick.unwrap_or(defaults.days_per_tick))
+        .map_err(|message| invalid_setting("ephemeris", "days_per_tick", message))?;
+    if days_per_tick <= 0.0 {
+        return Err(invalid_setting("ephemeris", "days_per_tick", "must be greater than zero"));
+    }
+    Ok(EphemerisSettings {
+        enabled: raw.enabled.unwrap_or(defaults.enabled),
+        start,
+        days_per_tick,
+    })
+}
+
+fn validate_flight(raw: RawFlight) -> Result<FlightSettings, SceneError> {
+    let defaults = FlightSettings::default();
+    let mode = match raw.mode.as_deref() {
+        None => defaults.mode,
+        Some("arcade") => FlightMode::Arcade,
+        Some("realistic") => FlightMode::Realistic,
+        Some(other) => {
+            return Err(invalid_setting("flight", "mode", format!("'{other}' is not one of arcade, realistic")))
+        }
+    };
+    let not_negative = |field: &'static str, value: Option<f32>, default: f32| {
+        match finite(value.unwrap_or(default)).map_err(|message| invalid_setting("flight", field, message))? {
+            value if value < 0.0 => Err(invalid_setting("flight", field, "must not be negative")),
+            value => Ok(value),
+        }
+    };
+    let damping = not_negative("damping", raw.damping, defaults.damping)?;
+    if damping > 1.0 {
+        return Err(invalid_setting("flight", "damping", "must be at most 1"));
+    }
+    Ok(FlightSettings {
+        mode,
+        main_thrust: not_negative("main_thrust", raw.main_thrust, defaults.main_thrust)?,
+        rcs_thrust: not_negative("rcs_thrust", raw.rcs_thrust, defaults.rcs_thrust)?,
+        turn_acceleration: not_negative("turn_acceleration", raw.turn_acceleration, defaults.turn_acceleration)?,
+        damping,
+    })
+}
+
+fn validate_collisions(raw: RawCollisions) -> Result<CollisionSettings, SceneError> {
+    let defaults = CollisionSettings::default();
+    let response = match raw.response.as_deref() {
+        None => defaults.response,
+        Some("bounce") => CollisionResponse::Bounce,
+        Some("stop") => CollisionResponse::Stop,
+        Some("respawn") => CollisionResponse::Respawn,
+        Some(other) => {
+            return Err(invalid_setting("collisions", "response", format!("'{other}' is not one of bounce, stop, respawn")))
+        }
+    };
+    let restitution = finite(raw.restitution.unwrap_or(defaults.restitution))
+        .map_err(|message| invalid_setting("collisions", "restitution", message))?;
+    if !(0.0..=1.0).contains(&restitution) {
+        return Err(invalid_setting("collisions", "restitution", "must be from 0 to 1"));
+    }
+    Ok(CollisionSettings {
+        enabled: raw.enabled.unwrap_or(defaults.enabled),
+        response,
+        restitution,
+    })
+}
+
+fn validate_body(
+    index: usize,
+    raw: RawBody,
+    meshes: &HashMap<String, MeshSource>,
+    previous: &[CelestialBody],
+) -> Result<CelestialBody, SceneError> {
+    // Bodies without an id are named by their position in the file
+    let id = match raw.id {
+        Some(id) if !id.trim().is_empty() => id,
+        Some(_) => return Err(invalid(&format!("#{index}"), "id", "must not be empty")),
+        None => return Err(invalid(&format!("#{index}"), "id", "is required")),
+    };
+    if previous.iter().any(|body| body.id == id) {
+        return Err(invalid(&id, "id", "is used by another body"));
+    }
+
+    let mesh = required(&id, "mesh", raw.mesh)?;
+    if !meshes.contains_key(&mesh) {
+        return Err(invalid(&id, "mesh", format!("'{mesh}' is not declared in [meshes]")));
+    }
+    // Files are not checked here, missing ones are replaced by placeholders when loading
+    let texture_path = raw.texture;
+    let normalmap_path = raw.normal_map;
+
+    let number = |field: &'static str, value: f32| finite(value).map_err(|message| invalid(&id, field, message));
+    for (field, vector) in [("position", raw.position), ("rotation", raw.rotation), ("velocity", raw.velocity)] {
+        for component in vector.into_iter().flatten() {
+            number(field, component)?;
+        }
+    }
+
+    let scale = number("scale", raw.scale.unwrap_or(1.0))?;
+    if scale <= 0.0 {
+        return Err(invalid(&id, "scale", "must be greater than zero"));
+    }
+
+    let cull_mode = match raw.cull_mode.as_deref() {
+        None | Some("back") => CullMode::Back,
+        Some("front") => CullMode::Front,
+        Some("none") => CullMode::None,
+        Some(other) => {
+            return Err(invalid(&id, "cull_mode", format!("'{other}' is not one of back, front, none")))
+        }
+    };
+    let blend_mode = match raw.blend_mode.as_deref() {
+        None | Some("opaque") => BlendMode::Opaque,
+        Some("alpha") => BlendMode::Alpha,
+        Some(other) => {
+            return Err(invalid(&id, "blend_mode", format!("'{other}' is not one of opaque, alpha")))
+        }
+    };
+
+    let shininess = number("shininess", raw.shininess.unwrap_or(16.0))?;
+    if shininess < 0.0 {
+        return Err(invalid(&id, "shininess", "must not be negative"));
+    }
+    let specular_strength = number("specular_strength", raw.specular_strength.unwrap_or(0.2))?;
+    if specular_strength < 0.0 {
+        return Err(invalid(&id, "specular_strength", "must not be negative"));
+    }
+
+    let day = match raw.rotation_period {
+        Some(period) => angular_speed(&id, "rotation_period", period)?,
+        None => 0.0,
+    };
+
+    let parent = match raw.parent {
+        Some(parent) if parent == id => return Err(invalid(&id, "parent", "a body cannot orbit itself")),
+        Some(parent) => match previous.iter().find(|body| body.id == parent) {
+            Some(found) => Some(found),
+            None => {
+                return Err(invalid(
+                    &id,
+                    "parent",
+                    format!("'{parent}' must be a body declared before this one"),
+                ))
+            }
+        },
+        None => None,
+    };
+    let center = parent.map_or(Vec3::zeros(), |parent| parent.translation);
+
+    // Without an orbit the body stays at `position` from its parent. The scene
+    // graph works out the world position, this one ignores the parent's tilt.
+    let position = raw.position.map_or(Vec3::zeros(), Vec3::from);
+    let (orbit, translation) = match raw.orbit {
+        Some(orbit) => {
+            if raw.position.is_some() {
+                return Err(invalid(&id, "position", "cannot be set on a body with an orbit"));
+            }
+            let orbit = validate_orbit(&id, orbit)?;
+            (Some(orbit), center + orbit.position(0.0))
+        }
+        None => (None, center + position),
+    };
+
+    let mass = number("mass", raw.mass.unwrap_or(0.0))?;
+    if mass < 0.0 {
+        return Err(invalid(&id, "mass", "must not be negative"));
+    }
+    // Bodies on an orbit start with the orbit's velocity
+    if raw.velocity.is_some() && orbit.is_some() {
+        return Err(invalid(&id, "velocity", "cannot be set on a body with an orbit"));
+    }
+
+    Ok(CelestialBody {
+        orbit,
+        position,
+        translation,
+        mass,
+        velocity: raw.velocity.map(Vec3::from),
+        scale,
+        collides: raw.collides.unwrap_or(true),
+        rotation: raw.rotation.map_or(Vec3::zeros(), Vec3::from),
+        day,
+        texture_path,
+        normalmap_path,
+        mesh,
+        parent: parent.map(|parent| parent.id.clone()),
+        cull_mode,
+        blend_mode,
+        shininess,
+        specular_strength,
+        id,
+    })
+}
+
+fn validate_orbit(id: &str, raw: RawOrbit) -> Result<OrbitalElements, SceneError> {
+    let number = |field: &'static str, value: f32| finite(value).map_err(|message| invalid(id, field, message));
+    let semi_major_axis = raw
+        .semi_major_axis
+        .ok_or_else(|| invalid(id, "orbit.semi_major_axis", "is required"))?;
+    if number("orbit.semi_major_axis", semi_major_axis)? < 0.0 {
+        return Err(invalid(id, "orbit.semi_major_axis", "must not be negative"));
+    }
+    let period = raw.period.ok_or_else(|| invalid(id, "orbit.period", "is required"))?;
+    let eccentricity = number("orbit.eccentricity", raw.eccentricity.unwrap_or(0.0))?;
+    // Parabolic and hyperbolic paths never come back, they are not orbits to draw
+    if !(0.0..1.0).contains(&eccentricity) {
+        return Err(invalid(id, "orbit.eccentricity", "must be at least 0 and less than 1"));
+    }
+    let angle = |field: &'static str, degrees: Option<f32>| {
+        number(field, degrees.unwrap_or(0.0)).map(f32::to_radians)
+    };
+    Ok(OrbitalElements {
+        eccentricity,
+        inclination: angle("orbit.inclination", raw.inclination)?,
+        ascending_node: angle("orbit.ascending_node", raw.ascending_node)?,
+        argument_of_periapsis: angle("orbit.argument_of_periapsis", raw.argument_of_periapsis)?,
+        mean_anomaly_at_epoch: angle("orbit.mean_anomaly", raw.mean_anomaly)?,
+        ..OrbitalElements::circular(semi_major_axis, angular_speed(id, "orbit.period", period)?)
+    })
+}
+
+fn invalid(body: &str, field: &'static str, message: impl Into<String>) -> SceneError {
+    SceneError::Invalid {
+        body: body.to_string(),
+        field,
+        message: message.into(),
+    }
+}
+
+fn invalid_mesh(mesh: &str, field: &'static str, message: impl Into<String>) -> SceneError {
+    SceneError::InvalidMesh {
+        mesh: mesh.to_string(),
+        field,
+        message: message.into(),
+    }
+}
+
+fn invalid_setting(table: &'static str, field: &'static str, message: impl Into<String>) -> SceneError {
+    SceneError::InvalidSetting {
+        table,
+        field,
+        message: message.into(),
+    }
+}
+
+fn required(body: &str, field: &'static str, value: Option<String>) -> Result<String, SceneError> {
+    value.ok_or_else(|| invalid(body, field, "is required"))
+}
+
+// Every number read from the file goes through here first, a NaN or infinity
+// would spread into every transform, orbit or shading term computed from it
+fn finite<T: Copy + Into<f64>>(value: T) -> Result<T, &'static str> {
+    if value.into().is_finite() {
+        Ok(value)
+    } else {
+        Err("must be a finite number")
+    }
+}
+
+// Periods are given in ticks for a full turn, the simulation works in radians per tick
+fn angular_speed(body: &str, field: &'static str, period: f32) -> Result<f32, SceneError> {
+    if finite(period).map_err(|message| invalid(body, field, message))? > 0.0 {
+        Ok(2.0 * PI / period)
+    } else {
+        Err(invalid(body, field, "must be greater than zero"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESHES: &str = r#"
+        [meshes]
+        planet = "./assets/3d_models/planet.obj"
+    "#;
+
+    fn body(fields: &str) -> String {
+        format!(
+            r#"{MESHES}
+            [[bodies]]
+            id = "sun"
+            mesh = "planet"
+            texture = "./assets/textures/sun.jpg"
+            normal_map = "./assets/normal_maps/moon_np.jpg"
+
+            [[bodies]]
+            id = "earth"
+            mesh = "planet"
+            texture = "./assets/textures/earth.jpg"
+            normal_map = "./assets/normal_maps/earth_np.jpg"
+            {fields}
+            "#
+        )
+    }
+
+    fn error_location(source: &str) -> (String, &'static str) {
+        match parse_scene(source, "test.toml") {
+            Err(SceneError::Invalid { body, field, .. }) => (body, field),
+            other => panic!("expected a validation error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn default_scene_loads() {
+        let scene = load_scene(DEFAULT_SCENE).unwrap();
+        let rings = scene.bodies.iter().find(|body| body.id == "rings").unwrap();
+        assert_eq!(rings.parent.as_deref(), Some("saturn"));
+        assert_eq!(rings.blend_mode, BlendMode::Alpha);
+        assert!(!rings.collides);
+    }
+
+    #[test]
+    fn orbit_periods_become_angular_speeds() {
+        let scene = parse_scene(
+            &body("rotation_period = 100\norbit = { radius = 7.6, period = 280 }"),
+            "test.toml",
+        )
+        .unwrap();
+        let earth = &scene.bodies[1];
+        assert!((earth.day - PI / 50.0).abs() < 1e-6);
+        assert!((earth.orbit.unwrap().mean_motion - PI / 140.0).abs() < 1e-6);
+        assert_eq!(earth.translation, Vec3::new(7.6, 0.0, 0.0));
+    }
+
+    #[test]
+    fn orbital_elements_are_read_in_degrees() {
+        let scene = parse_scene(
+            &body(
+                "orbit = { semi_major_axis = 5.0, period = 400, eccentricity = 0.2, \
+                 inclination = 7.0, ascending_node = 48.0, argument_of_periapsis = 29.0, mean_anomaly = 180.0 }",
+            ),
+            "test.toml",
+        )
+        .unwrap();
+        let orbit = scene.bodies[1].orbit.unwrap();
+        assert!((orbit.inclination - 7.0_f32.to_radians()).abs() < 1e-6);
+        assert!((orbit.mean_anomaly_at_epoch - PI).abs() < 1e-6);
+        // Starting at the apoapsis
+        assert!((scene.bodies[1].translation.norm() - 6.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn children_without_an_orbit_sit_at_their_position_from_the_parent() {
+        let source = body("parent = \"sun\"\nposition = [0.0, 2.0, 0.0]").replace(
+            "texture = \"./assets/textures/sun.jpg\"",
+            "texture = \"./assets/textures/sun.jpg\"\nposition = [5.0, 0.0, 0.0]",
+        );
+        let scene = parse_scene(&source, "test.toml").unwrap();
+        let earth = &scene.bodies[1];
+        assert!(earth.orbit.is_none());
+        assert_eq!(earth.position, Vec3::new(0.0, 2.0, 0.0));
+        assert_eq!(earth.translation, Vec3::new(5.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn errors_name_the_body_and_field() {
+        let cases = [
+            ("scale = -1.0", "scale"),
+            ("scale = nan", "scale"),
+            ("position = [0.0, nan, 0.0]", "position"),
+            ("rotation = [inf, 0.0, 0.0]", "rotation"),
+            ("rotation_period = nan", "rotation_period"),
+            ("shininess = -1.0", "shininess"),
+            ("shininess = inf", "shininess"),
+            ("specular_strength = -0.5", "specular_strength"),
+            ("specular_strength = nan", "specular_strength"),
+            ("cull_mode = \"sideways\"", "cull_mode"),
+            ("parent = \"moon\"\norbit = { radius = 1.0, period = 10 }", "parent"),
+            ("orbit = { radius = 1.0, period = 0 }", "orbit.period"),
+            ("orbit = { period = 10 }", "orbit.semi_major_axis"),
+            ("orbit = { radius = inf, period = 10 }", "orbit.semi_major_axis"),
+            ("orbit = { radius = 1.0, period = 10, eccentricity = 1.0 }", "orbit.eccentricity"),
+            ("orbit = { radius = 1.0, period = 10, eccentricity = nan }", "orbit.eccentricity"),
+            ("orbit = { radius = 1.0, period = 10, inclination = nan }", "orbit.inclination"),
+            ("orbit = { radius = 1.0, period = 10, ascending_node = inf }", "orbit.ascending_node"),
+            ("orbit = { radius = 1.0, period = 10, argument_of_periapsis = nan }", "orbit.argument_of_periapsis"),
+            ("orbit = { radius = 1.0, period = 10, mean_anomaly = inf }", "orbit.mean_anomaly"),
+            ("parent = \"sun\"\nposition = [1.0, 0.0, 0.0]\norbit = { radius = 1.0, period = 10 }", "position"),
+        ];
+        for (fields, expected) in cases {
+            assert_eq!(error_location(&body(fields)), ("earth".to_string(), expected), "{fields}");
+        }
+
+        let unknown_mesh = body("").replace("planet = ", "cube = ");
+        assert_eq!(error_location(&unknown_mesh), ("sun".to_string(), "mesh"));
+        let duplicate = body("").replace("id = \"earth\"", "id = \"sun\"");
+        assert_eq!(error_location(&duplicate), ("sun".to_string(), "id"));
+    }
+
+    #[test]
+    fn meshes_can_set_generation_options() {
+        let source = body("").replace(
+            "planet = \"./assets/3d_models/planet.obj\"",
+            "planet = { path = \"./assets/3d_models/planet.obj\", normals = \"flat\", uv_projection = \"planar\" }",
+        );
+        let scene = parse_scene(&source, "test.toml").unwrap();
+        let options = scene.meshes["planet"].options;
+        assert_eq!((options.normals, options.uv_projection), (NormalGeneration::Flat, UvProjection::Planar));
+
+        let unknown = source.replace("\"flat\"", "\"faceted\"");
+        assert!(matches!(
+            parse_scene(&unknown, "test.toml"),
+            Err(SceneError::InvalidMesh { field: "normals", .. })
+        ));
+        let conflicting = format!("{source}\n[meshes.rock]\npath = \"./assets/3d_models/planet.obj\"\n");
+        assert!(matches!(
+            parse_scene(&conflicting, "test.toml"),
+            Err(SceneError::InvalidMesh { field: "path", .. })
+        ));
+    }
+
+    #[test]
+    fn gravity_settings_and_masses_are_read() {
+        let source = format!(
+            "[gravity]\nenabled = true\nsubsteps = 8\n{}",
+            body("mass = 0.5\nvelocity = [0.0, 0.0, 1.0]")
+        );
+        let scene = parse_scene(&source, "test.toml").unwrap();
+        assert!(scene.gravity.enabled);
+        assert_eq!(scene.gravity.substeps, 8);
+        assert_eq!(scene.gravity.gravitational_constant, 1.0);
+        assert_eq!(scene.bodies[1].mass, 0.5);
+        assert_eq!(scene.bodies[1].velocity, Some(Vec3::new(0.0, 0.0, 1.0)));
+
+        let no_steps = format!("[gravity]\nsubsteps = 0\n{}", body(""));
+        assert!(matches!(
+            parse_scene(&no_steps, "test.toml"),
+            Err(SceneError::InvalidSetting { table: "gravity", field: "substeps", .. })
+        ));
+        let no_constant = format!("[gravity]\ngravitational_constant = nan\n{}", body(""));
+        assert!(matches!(
+            parse_scene(&no_constant, "test.toml"),
+            Err(SceneError::InvalidSetting { table: "gravity", field: "gravitational_constant", .. })
+        ));
+        for mass in ["mass = -1.0", "mass = inf"] {
+            assert_eq!(error_location(&body(mass)), ("earth".to_string(), "mass"), "{mass}");
+        }
+        assert_eq!(error_location(&body("velocity = [0.0, 0.0, nan]")), ("earth".to_string(), "velocity"));
+        let orbit_and_velocity = body("velocity = [1.0, 0.0, 0.0]\norbit = { radius = 1.0, period = 10 }");
+        assert_eq!(error_location(&orbit_and_velocity), ("earth".to_string(), "velocity"));
+    }
+
+    #[test]
+    fn ephemeris_dates_become_julian_dates() {
+        let source = format!("[ephemeris]\nenabled = true\ndate = \"2024-04-08T18:00\"\n{}", body(""));
+        let ephemeris = parse_scene(&source, "test.toml").unwrap().ephemeris;
+        assert!(ephemeris.enabled);
+        assert_eq!(ephemeris.start, 2460409.25);
+        assert_eq!(ephemeris.days_per_tick, 1.0);
+
+        let bad_date = format!("[ephemeris]\ndate = \"2024-02-30\"\n{}", body(""));
+        assert!(matches!(
+            parse_scene(&bad_date, "test.toml"),
+            Err(SceneError::InvalidSetting { table: "ephemeris", field: "date", .. })
+        ));
+        for days in ["0.0", "nan", "inf"] {
+            let source = format!("[ephemeris]\ndays_per_tick = {days}\n{}", body(""));
+            assert!(matches!(
+                parse_scene(&source, "test.toml"),
+                Err(SceneError::InvalidSetting { table: "ephemeris", field: "days_per_tick", .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn flight_settings_are_read() {
+        let source = format!("[flight]\nmode = \"realistic\"\nmain_thrust = 0.5\n{}", body(""));
+        let flight = parse_scene(&source, "test.toml").unwrap().flight;
+        assert_eq!(flight.mode, FlightMode::Realistic);
+        assert_eq!(flight.main_thrust, 0.5);
+        assert_eq!(flight.damping, FlightSettings::default().damping);
+
+        let cases = [
+            ("mode = \"hover\"", "mode"),
+            ("rcs_thrust = -1.0", "rcs_thrust"),
+            ("main_thrust = nan", "main_thrust"),
+            ("turn_acceleration = inf", "turn_acceleration"),
+            ("damping = 2.0", "damping"),
+            ("damping = nan", "damping"),
+        ];
+        for (table, field) in cases {
+            let source = format!("[flight]\n{table}\n{}", body(""));
+            assert!(matches!(
+                parse_scene(&source, "test.toml"),
+                Err(SceneError::InvalidSetting { table: "flight", field: f, .. }) if f == field
+            ));
+        }
+    }
+
+    #[test]
+    fn collision_settings_are_read() {
+        let source = format!("[collisions]\nresponse = \"respawn\"\n{}", body(""));
+        let collisions = parse_scene(&source, "test.toml").unwrap().collisions;
+        assert!(collisions.enabled);
+        assert_eq!(collisions.response, CollisionResponse::Respawn);
+
+        let cases = [
+            ("response = \"explode\"", "response"),
+            ("restitution = 1.5", "restitution"),
+            ("restitution = nan", "restitution"),
+        ];
+        for (table, field) in cases {
+            let source = format!("[collisions]\n{table}\n{}", body(""));
+            assert!(matches!(
+                parse_scene(&source, "test.toml"),
+                Err(SceneError::InvalidSetting { table: "collisions", field: f, .. }) if f == field
+            ));
+        }
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        assert!(matches!(parse_scene(&body("colour = 3"), "test.toml"), Err(SceneError::Parse { .. })));
+    }
+}
@@ -1,57 +1,61 @@
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use once_cell::sync::Lazy;
+use image::RgbaImage;
+use nalgebra_glm::Vec2;
+use crate::loaders::error::LoadError;
+use crate::loaders::mipmap::{FilterMode, MipChain};
 use crate::screen::color::Color;
 
-static TEXTURES: Lazy<Mutex<HashMap<String, Arc<Texture>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
-
-
+// Size of the generated fallback texture and of one of its squares
+const CHECKERBOARD_SIZE: u32 = 64;
+const CHECKER_SIZE: u32 = 8;
 
 #[derive(Clone, Debug)]
 pub struct Texture {
-    width: u32,
-    height: u32,
-    data: Vec<Color>,
+    mips: MipChain<Color>,
 }
 
 impl Texture {
-    pub fn new(path: &str) -> Result<Self, image::ImageError> {
-        let img = image::open(path)?.to_rgba8();
+    pub fn new(path: &str) -> Result<Self, LoadError> {
+        let img = image::open(path)
+            .map_err(|source| LoadError::Image { path: path.to_string(), source })?
+            .to_rgba8();
+        Ok(Texture::from_image(&img))
+    }
+
+    pub fn from_image(img: &RgbaImage) -> Self {
         let (width, height) = img.dimensions();
         let data = img.pixels()
-            .map(|p| Color::new(p[0] as i32, p[1] as i32, p[2] as i32))
+            .map(|p| Color::new_rgba(p[0] as i32, p[1] as i32, p[2] as i32, p[3] as i32))
             .collect();
 
-        Ok(Texture {
-            width,
-            height,
-            data,
-        })
+        Texture {
+            mips: MipChain::new(width, height, data),
+        }
     }
 
-    pub fn sample(&self, u: f32, v: f32) -> Color {
-        let u = u.fract().abs();
-        let v = v.fract().abs();
-        
-        let x = (u * (self.width as f32)) as u32;
-        let y = (v * (self.height as f32)) as u32;
-        
-        let index = ((self.height-y-1) * self.width + x) as usize;
-        self.data[index]
+    // Magenta and black squares, stands in for textures that failed to load
+    pub fn checkerboard() -> Self {
+        let size = CHECKERBOARD_SIZE;
+        let data = (0..size * size)
+            .map(|i| {
+                let (x, y) = (i % size / CHECKER_SIZE, i / size / CHECKER_SIZE);
+                if (x + y) % 2 == 0 {
+                    Color::new(255, 0, 255)
+                } else {
+                    Color::new(0, 0, 0)
+                }
+            })
+            .collect();
+
+        Texture {
+            mips: MipChain::new(size, size, data),
+        }
     }
-}
 
-// Initialize and store a texture with a given identifier
-pub fn init_texture(id: &str, path: &str) -> Result<(), image::ImageError> {
-    let texture = Texture::new(path)?;
-    let mut textures = TEXTURES.lock().unwrap();
-    textures.insert(id.to_string(), Arc::new(texture));
-    Ok(())
-}
+    pub fn level_of_detail(&self, uv_dx: Vec2, uv_dy: Vec2) -> f32 {
+        self.mips.level_of_detail(uv_dx, uv_dy)
+    }
 
-// Retrieve a texture by its identifier and apply a function to it
-pub fn with_texture(id: &str, f: impl FnOnce(&Texture) -> Color) -> Color {
-    let textures = TEXTURES.lock().unwrap();
-    let texture = textures.get(id).expect("Texture not initialized");
-    f(texture)
-}
\ No newline at end of file
+    pub fn sample(&self, u: f32, v: f32, lod: f32, filter: FilterMode) -> Color {
+        self.mips.sample(u, v, lod, filter)
+    }
+}
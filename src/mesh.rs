@@ -0,0 +1,273 @@
+use std::f32::consts::PI;
+
+use nalgebra_glm::{Vec2, Vec3};
+
+use crate::vertex::Vertex;
+
+// How normals are made up for meshes that come without them
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NormalGeneration {
+    // Averaged over the triangles sharing a vertex, for curved surfaces
+    #[default]
+    Smooth,
+    // One normal per triangle, for hard edged models
+    Flat,
+}
+
+// How texture coordinates are made up for meshes that come without them
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum UvProjection {
+    // Longitude and latitude around the center of the mesh, like Mesh::uv_sphere
+    #[default]
+    Spherical,
+    // Straight down the axis the mesh is thinnest along
+    Planar,
+}
+
+// Indexed triangle mesh: every unique vertex is stored once and
+// triangles reference them by index, three indices per triangle
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    // Unit sphere with counter-clockwise outward faces, u wraps around the
+    // equator and v runs from the south pole (0) to the north pole (1)
+    pub fn uv_sphere(segments: u32, rings: u32) -> Self {
+        let mut vertices = Vec::with_capacity(((segments + 1) * (rings + 1)) as usize);
+        for ring in 0..=rings {
+            let theta = PI * ring as f32 / rings as f32;
+            for segment in 0..=segments {
+                let phi = 2.0 * PI * segment as f32 / segments as f32;
+                let position = Vec3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin());
+                vertices.push(Vertex {
+                    position,
+                    normal: position,
+                    tex_coords: Vec2::new(
+                        segment as f32 / segments as f32,
+                        1.0 - ring as f32 / rings as f32,
+                    ),
+                    ..Default::default()
+                });
+            }
+        }
+
+        let mut indices = Vec::with_capacity((segments * rings * 6) as usize);
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let top = ring * (segments + 1) + segment;
+                let bottom = top + segments + 1;
+                indices.extend_from_slice(&[top, top + 1, bottom, top + 1, bottom + 1, bottom]);
+            }
+        }
+
+        let mut mesh = Mesh { vertices, indices };
+        mesh.compute_tangents();
+        mesh
+    }
+
+    pub fn generate_normals(&mut self, generation: NormalGeneration) {
+        match generation {
+            NormalGeneration::Smooth => self.smooth_normals(),
+            NormalGeneration::Flat => self.flat_normals(),
+        }
+    }
+
+    // Sums the face normals around every vertex, the cross product already
+    // weights them by the triangle's area
+    fn smooth_normals(&mut self) {
+        let mut normals = vec![Vec3::zeros(); self.vertices.len()];
+        for triangle in self.triangles() {
+            let normal = self.face_normal(triangle);
+            for i in triangle {
+                normals[i] += normal;
+            }
+        }
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+            vertex.normal = normal.try_normalize(f32::EPSILON).unwrap_or_else(Vec3::z);
+        }
+    }
+
+    // Every triangle gets vertices of its own so its edges stay sharp
+    fn flat_normals(&mut self) {
+        let triangles: Vec<[usize; 3]> = self.triangles().collect();
+        let mut vertices = Vec::with_capacity(self.indices.len());
+        for triangle in triangles {
+            let normal = self.face_normal(triangle).try_normalize(f32::EPSILON).unwrap_or_else(Vec3::z);
+            for i in triangle {
+                vertices.push(Vertex { normal, ..self.vertices[i].clone() });
+            }
+        }
+        self.indices = (0..vertices.len() as u32).collect();
+        self.vertices = vertices;
+    }
+
+    // Counter-clockwise triangles face the way the normal points
+    fn face_normal(&self, [i1, i2, i3]: [usize; 3]) -> Vec3 {
+        let (a, b, c) = (
+            self.vertices[i1].position,
+            self.vertices[i2].position,
+            self.vertices[i3].position,
+        );
+        (b - a).cross(&(c - a))
+    }
+
+    pub fn generate_uvs(&mut self, projection: UvProjection) {
+        if self.vertices.is_empty() {
+            return;
+        }
+        let (mut min, mut max) = (self.vertices[0].position, self.vertices[0].position);
+        for vertex in &self.vertices {
+            min = min.inf(&vertex.position);
+            max = max.sup(&vertex.position);
+        }
+        let center = (min + max) / 2.0;
+        let size = max - min;
+
+        // The two widest axes span the plane, thinnest first in the list is dropped
+        let mut axes = [0, 1, 2];
+        axes.sort_by(|&a, &b| size[a].total_cmp(&size[b]));
+        let (u_axis, v_axis) = (axes[1].min(axes[2]), axes[1].max(axes[2]));
+        let scale = |value: f32, axis: usize| {
+            if size[axis] > f32::EPSILON { (value - min[axis]) / size[axis] } else { 0.0 }
+        };
+
+        for vertex in self.vertices.iter_mut() {
+            vertex.tex_coords = match projection {
+                UvProjection::Spherical => {
+                    let direction = (vertex.position - center).try_normalize(f32::EPSILON).unwrap_or_else(Vec3::y);
+                    Vec2::new(
+                        (direction.z.atan2(direction.x) / (2.0 * PI)).rem_euclid(1.0),
+                        0.5 + direction.y.clamp(-1.0, 1.0).asin() / PI,
+                    )
+                }
+                UvProjection::Planar => Vec2::new(
+                    scale(vertex.position[u_axis], u_axis),
+                    scale(vertex.position[v_axis], v_axis),
+                ),
+            };
+        }
+    }
+
+    pub fn triangles(&self) -> impl Iterator<Item = [usize; 3]> + '_ {
+        self.indices
+            .chunks_exact(3)
+            .map(|tri| [tri[0] as usize, tri[1] as usize, tri[2] as usize])
+    }
+
+    // Per-vertex tangent frames for tangent-space normal mapping. Tangents are
+    // accumulated from the UV gradients of every adjacent triangle and then
+    // orthogonalized against the vertex normal.
+    pub fn compute_tangents(&mut self) {
+        let mut tangents = vec![Vec3::zeros(); self.vertices.len()];
+        let mut bitangents = vec![Vec3::zeros(); self.vertices.len()];
+
+        for [i1, i2, i3] in self.triangles() {
+            let (v1, v2, v3) = (&self.vertices[i1], &self.vertices[i2], &self.vertices[i3]);
+            let edge1 = v2.position - v1.position;
+            let edge2 = v3.position - v1.position;
+            let delta_uv1 = v2.tex_coords - v1.tex_coords;
+            let delta_uv2 = v3.tex_coords - v1.tex_coords;
+
+            let determinant = delta_uv1.x * delta_uv2.y - delta_uv2.x * delta_uv1.y;
+            if determinant.abs() < f32::EPSILON {
+                continue;
+            }
+            let r = 1.0 / determinant;
+            let tangent = (edge1 * delta_uv2.y - edge2 * delta_uv1.y) * r;
+            let bitangent = (edge2 * delta_uv1.x - edge1 * delta_uv2.x) * r;
+            for i in [i1, i2, i3] {
+                tangents[i] += tangent;
+                bitangents[i] += bitangent;
+            }
+        }
+
+        for (i, vertex) in self.vertices.iter_mut().enumerate() {
+            let normal = vertex.normal;
+            // Gram-Schmidt, falling back to any perpendicular axis when UVs are degenerate
+            let mut tangent = tangents[i] - normal * normal.dot(&tangents[i]);
+            if tangent.norm_squared() < f32::EPSILON {
+                let axis = if normal.x.abs() < 0.9 { Vec3::x() } else { Vec3::y() };
+                tangent = axis - normal * normal.dot(&axis);
+            }
+            let tangent = tangent.normalize();
+            // Keep the UV handedness so mirrored mappings stay correct
+            let handedness = if normal.cross(&tangent).dot(&bitangents[i]) < 0.0 { -1.0 } else { 1.0 };
+            vertex.tangent = tangent;
+            vertex.bitangent = normal.cross(&tangent) * handedness;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uv_sphere_faces_point_outwards() {
+        let sphere = Mesh::uv_sphere(16, 8);
+        for [i1, i2, i3] in sphere.triangles() {
+            let (a, b, c) = (
+                sphere.vertices[i1].position,
+                sphere.vertices[i2].position,
+                sphere.vertices[i3].position,
+            );
+            let normal = (b - a).cross(&(c - a));
+            // Triangles touching the poles collapse to a line
+            if normal.norm() > 1e-6 {
+                assert!(normal.dot(&(a + b + c)) > 0.0);
+            }
+        }
+    }
+
+    // Two triangles folded 90 degrees along the edge from (0,0,0) to (0,1,0)
+    fn folded_quad() -> Mesh {
+        let positions = [
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        ];
+        Mesh {
+            vertices: positions
+                .iter()
+                .map(|&position| Vertex { position, ..Default::default() })
+                .collect(),
+            indices: vec![0, 1, 2, 0, 3, 1],
+        }
+    }
+
+    #[test]
+    fn smooth_normals_are_shared_and_flat_normals_are_not() {
+        let mut smooth = folded_quad();
+        smooth.generate_normals(NormalGeneration::Smooth);
+        let between = Vec3::new(0.0, 0.0, 1.0) + Vec3::new(1.0, 0.0, 0.0);
+        assert!((smooth.vertices[0].normal - between.normalize()).norm() < 1e-6);
+        assert!((smooth.vertices[2].normal - Vec3::new(0.0, 0.0, 1.0)).norm() < 1e-6);
+
+        let mut flat = folded_quad();
+        flat.generate_normals(NormalGeneration::Flat);
+        assert_eq!(flat.vertices.len(), 6);
+        assert!((flat.vertices[0].normal - Vec3::new(0.0, 0.0, 1.0)).norm() < 1e-6);
+        assert!((flat.vertices[3].normal - Vec3::new(1.0, 0.0, 0.0)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn generated_uvs_match_the_projection() {
+        let mut sphere = Mesh::uv_sphere(16, 8);
+        let expected: Vec<Vec2> = sphere.vertices.iter().map(|vertex| vertex.tex_coords).collect();
+        sphere.generate_uvs(UvProjection::Spherical);
+        // The seam and the poles have several valid coordinates
+        for (vertex, expected) in sphere.vertices.iter().zip(expected).skip(17).take(15) {
+            assert!((vertex.tex_coords - expected).norm() < 1e-4, "{vertex:?}");
+        }
+
+        let mut plane = folded_quad();
+        plane.vertices[3].position = Vec3::new(-1.0, 1.0, 0.0);
+        plane.generate_uvs(UvProjection::Planar);
+        let uvs: Vec<Vec2> = plane.vertices.iter().map(|vertex| vertex.tex_coords).collect();
+        assert_eq!(uvs, vec![Vec2::new(1.0, 0.0), Vec2::new(1.0, 1.0), Vec2::new(0.0, 0.0), Vec2::new(0.0, 1.0)]);
+    }
+}
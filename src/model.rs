@@ -0,0 +1,70 @@
+use image::RgbaImage;
+
+use crate::mesh::Mesh;
+use crate::screen::color::Color;
+
+// Surface description shared by the OBJ/MTL and glTF loaders. Maps are file
+// paths, missing values fall back to what the body itself declares.
+#[derive(Clone, Debug)]
+pub struct Material {
+    // Multiplies the diffuse map, or is the whole color without one
+    pub diffuse: Color,
+    // Weight of the specular highlight
+    pub specular_strength: Option<f32>,
+    pub shininess: Option<f32>,
+    pub diffuse_map: Option<String>,
+    pub normal_map: Option<String>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            diffuse: Color::new(255, 255, 255),
+            specular_strength: None,
+            shininess: None,
+            diffuse_map: None,
+            normal_map: None,
+        }
+    }
+}
+
+// A piece of a model drawn with a single material
+#[derive(Clone, Debug)]
+pub struct ModelPart {
+    pub name: String,
+    pub mesh: Mesh,
+    pub material: Material,
+}
+
+// Image stored inside a model file rather than next to it. Materials refer to
+// it by `key` ("ship.glb#image0"), which the asset stores treat like a path.
+#[derive(Clone, Debug)]
+pub struct EmbeddedImage {
+    pub key: String,
+    pub image: RgbaImage,
+}
+
+// Everything a body is drawn with, one part per object/material pair of the file
+#[derive(Clone, Debug)]
+pub struct Model {
+    pub parts: Vec<ModelPart>,
+    pub images: Vec<EmbeddedImage>,
+}
+
+impl Model {
+    pub fn image(&self, key: &str) -> Option<&RgbaImage> {
+        self.images.iter().find(|image| image.key == key).map(|image| &image.image)
+    }
+
+    // Single part model with the default material
+    pub fn from_mesh(name: &str, mesh: Mesh) -> Self {
+        Model {
+            parts: vec![ModelPart {
+                name: name.to_string(),
+                mesh,
+                material: Material::default(),
+            }],
+            images: Vec::new(),
+        }
+    }
+}
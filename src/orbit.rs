@@ -0,0 +1,177 @@
+use std::f32::consts::PI;
+
+use nalgebra_glm::Vec3;
+
+// Newton's method gets within this many radians of the eccentric anomaly
+const KEPLER_TOLERANCE: f32 = 1e-6;
+const KEPLER_MAX_ITERATIONS: usize = 32;
+
+// Keplerian elements of an elliptical orbit around the parent body, or the
+// origin without one. Angles are in radians and measured in the world's XZ
+// plane, which plays the part of the ecliptic, with y as its north.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrbitalElements {
+    pub semi_major_axis: f32,
+    // 0 is a circle, values up to (not including) 1 an ever longer ellipse
+    pub eccentricity: f32,
+    // Tilt of the orbit against the reference plane
+    pub inclination: f32,
+    // Longitude of the ascending node, where the orbit rises through the plane
+    pub ascending_node: f32,
+    // Angle from the ascending node to the periapsis, in the orbit's own plane
+    pub argument_of_periapsis: f32,
+    // Mean anomaly at time 0
+    pub mean_anomaly_at_epoch: f32,
+    // Radians of mean anomaly per tick, 2π over the period
+    pub mean_motion: f32,
+}
+
+impl OrbitalElements {
+    // Circle in the reference plane starting on the x axis
+    pub fn circular(radius: f32, mean_motion: f32) -> Self {
+        OrbitalElements {
+            semi_major_axis: radius,
+            eccentricity: 0.0,
+            inclination: 0.0,
+            ascending_node: 0.0,
+            argument_of_periapsis: 0.0,
+            mean_anomaly_at_epoch: 0.0,
+            mean_motion,
+        }
+    }
+
+    pub fn mean_anomaly(&self, time: f32) -> f32 {
+        self.mean_anomaly_at_epoch + self.mean_motion * time
+    }
+
+    // Offset from the body being orbited at `time`, in ticks
+    pub fn position(&self, time: f32) -> Vec3 {
+        let (a, e) = (self.semi_major_axis, self.eccentricity);
+        let eccentric_anomaly = solve_kepler(self.mean_anomaly(time), e);
+        let (sin_e, cos_e) = eccentric_anomaly.sin_cos();
+
+        // In the orbital plane, with the periapsis on the x axis
+        let x = a * (cos_e - e);
+        let y = a * (1.0 - e * e).sqrt() * sin_e;
+        self.rotate_into_world(x, y)
+    }
+
+    // Velocity relative to the body being orbited at `time` when the pair has
+    // the gravitational parameter `mu` (G times their masses). The speed comes
+    // from `mu` rather than the period, which is what gravity would make it.
+    pub fn velocity(&self, time: f32, mu: f32) -> Vec3 {
+        let (a, e) = (self.semi_major_axis, self.eccentricity);
+        let eccentric_anomaly = solve_kepler(self.mean_anomaly(time), e);
+        let (sin_e, cos_e) = eccentric_anomaly.sin_cos();
+
+        let speed = (mu * a).sqrt() / (a * (1.0 - e * cos_e));
+        self.rotate_into_world(-speed * sin_e, speed * (1.0 - e * e).sqrt() * cos_e)
+    }
+
+    // From the orbital plane, periapsis on x, to the world
+    fn rotate_into_world(&self, x: f32, y: f32) -> Vec3 {
+        // Rotated by the argument of periapsis, the inclination and the node
+        let (sin_w, cos_w) = self.argument_of_periapsis.sin_cos();
+        let (sin_i, cos_i) = self.inclination.sin_cos();
+        let (sin_n, cos_n) = self.ascending_node.sin_cos();
+        let ecliptic_x = (cos_n * cos_w - sin_n * sin_w * cos_i) * x
+            + (-cos_n * sin_w - sin_n * cos_w * cos_i) * y;
+        let ecliptic_y = (sin_n * cos_w + cos_n * sin_w * cos_i) * x
+            + (-sin_n * sin_w + cos_n * cos_w * cos_i) * y;
+        let ecliptic_z = sin_w * sin_i * x + cos_w * sin_i * y;
+
+        // The ecliptic's north is the world's y axis
+        Vec3::new(ecliptic_x, ecliptic_z, ecliptic_y)
+    }
+}
+
+// Eccentric anomaly E of an elliptical orbit for the mean anomaly M, the
+// solution of Kepler's equation M = E - e sin E
+pub fn solve_kepler(mean_anomaly: f32, eccentricity: f32) -> f32 {
+    // Wrapped to [-π, π) so the iteration starts close to the answer
+    let mean_anomaly = (mean_anomaly + PI).rem_euclid(2.0 * PI) - PI;
+    // M itself is a good guess for round orbits, π converges for long ones
+    let mut eccentric_anomaly = if eccentricity < 0.8 {
+        mean_anomaly
+    } else {
+        PI.copysign(mean_anomaly)
+    };
+    for _ in 0..KEPLER_MAX_ITERATIONS {
+        let error = eccentric_anomaly - eccentricity * eccentric_anomaly.sin() - mean_anomaly;
+        let step = error / (1.0 - eccentricity * eccentric_anomaly.cos());
+        eccentric_anomaly -= step;
+        if step.abs() < KEPLER_TOLERANCE {
+            break;
+        }
+    }
+    eccentric_anomaly
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kepler_solutions_satisfy_the_equation() {
+        for eccentricity in [0.0, 0.2056, 0.5, 0.9, 0.99] {
+            for step in 0..64 {
+                let mean_anomaly = -PI + step as f32 * PI / 32.0;
+                let eccentric_anomaly = solve_kepler(mean_anomaly, eccentricity);
+                let residual = eccentric_anomaly - eccentricity * eccentric_anomaly.sin() - mean_anomaly;
+                assert!(residual.abs() < 1e-5, "e = {eccentricity}, M = {mean_anomaly}: {residual}");
+            }
+        }
+    }
+
+    #[test]
+    fn circular_orbits_turn_at_a_constant_rate_in_the_plane() {
+        let orbit = OrbitalElements::circular(7.6, PI / 140.0);
+        for time in [0.0, 35.0, 70.0, 200.0] {
+            let angle = orbit.mean_motion * time;
+            let expected = Vec3::new(7.6 * angle.cos(), 0.0, 7.6 * angle.sin());
+            assert!((orbit.position(time) - expected).norm() < 1e-4, "{time}");
+        }
+    }
+
+    #[test]
+    fn velocity_follows_the_position() {
+        let orbit = OrbitalElements {
+            eccentricity: 0.4,
+            inclination: 30.0_f32.to_radians(),
+            ascending_node: 50.0_f32.to_radians(),
+            argument_of_periapsis: 70.0_f32.to_radians(),
+            ..OrbitalElements::circular(10.0, 2.0 * PI / 500.0)
+        };
+        // With the gravitational parameter Kepler's third law gives for the period
+        let mu = orbit.semi_major_axis.powi(3) * orbit.mean_motion.powi(2);
+        for time in [0.0, 60.0, 250.0, 410.0] {
+            let step = 1.0;
+            let expected = (orbit.position(time + step) - orbit.position(time - step)) / (2.0 * step);
+            let velocity = orbit.velocity(time, mu);
+            assert!((velocity - expected).norm() < 1e-3 * expected.norm(), "{time}: {velocity:?} {expected:?}");
+        }
+    }
+
+    #[test]
+    fn elements_shape_and_tilt_the_ellipse() {
+        let orbit = OrbitalElements {
+            eccentricity: 0.25,
+            inclination: 17.0_f32.to_radians(),
+            ascending_node: 110.0_f32.to_radians(),
+            argument_of_periapsis: 114.0_f32.to_radians(),
+            ..OrbitalElements::circular(28.0, 2.0 * PI / 400.0)
+        };
+        // Periapsis at time 0, apoapsis half a period later
+        assert!((orbit.position(0.0).norm() - 28.0 * 0.75).abs() < 1e-3);
+        assert!((orbit.position(200.0).norm() - 28.0 * 1.25).abs() < 1e-3);
+
+        // Every point lies in the tilted plane, whose normal is the angular momentum
+        let normal = orbit.position(0.0).cross(&orbit.position(50.0)).normalize();
+        assert!((normal.y.abs() - orbit.inclination.cos()).abs() < 1e-4);
+        let highest = (0..400).map(|time| orbit.position(time as f32).y).fold(f32::MIN, f32::max);
+        assert!(highest > 0.0 && highest < 28.0 * 1.25 * orbit.inclination.sin() + 1e-3);
+        for time in (0..400).step_by(37) {
+            assert!(orbit.position(time as f32).dot(&normal).abs() < 1e-3);
+        }
+    }
+}
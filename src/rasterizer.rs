@@ -0,0 +1,174 @@
+use rayon::prelude::*;
+
+use crate::bounding_box::calculate_bounding_box;
+use crate::fragments::{triangle_fill, Fragment};
+use crate::screen::color::Color;
+use crate::screen::framebuffer::Framebuffer;
+use crate::vertex::Vertex;
+
+pub const TILE_SIZE: usize = 32;
+
+// How shaded fragments are merged into the framebuffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    // Overwrites the color and writes depth
+    Opaque,
+    // Composites over the existing color, depth tested but never written
+    Alpha,
+}
+
+// Screen region rasterized by a single worker, with the triangles binned into it
+struct Tile {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    triangles: Vec<usize>,
+}
+
+// Tile-local copy of the framebuffer region a worker writes into
+struct TileBuffer {
+    colors: Vec<Color>,
+    depths: Vec<f32>,
+}
+
+impl Tile {
+    fn clip_rect(&self) -> (i32, i32, i32, i32) {
+        (
+            self.x as i32,
+            self.y as i32,
+            (self.x + self.width) as i32,
+            (self.y + self.height) as i32,
+        )
+    }
+}
+
+impl TileBuffer {
+    fn read(framebuffer: &Framebuffer, tile: &Tile) -> Self {
+        let mut colors = Vec::with_capacity(tile.width * tile.height);
+        let mut depths = Vec::with_capacity(tile.width * tile.height);
+        for y in tile.y..tile.y + tile.height {
+            let row = y * framebuffer.width + tile.x;
+            colors.extend_from_slice(&framebuffer.buffer[row..row + tile.width]);
+            depths.extend_from_slice(&framebuffer.zbuffer[row..row + tile.width]);
+        }
+        TileBuffer { colors, depths }
+    }
+
+    fn merge(&mut self, index: usize, color: Color, depth: f32, blend_mode: BlendMode) {
+        match blend_mode {
+            BlendMode::Opaque => {
+                self.colors[index] = color;
+                self.depths[index] = depth;
+            }
+            BlendMode::Alpha => {
+                if color.a > 0 {
+                    self.colors[index] = color.blend_over(self.colors[index]);
+                }
+            }
+        }
+    }
+
+    fn write(&self, framebuffer: &mut Framebuffer, tile: &Tile) {
+        for local_y in 0..tile.height {
+            let row = (tile.y + local_y) * framebuffer.width + tile.x;
+            let local_row = local_y * tile.width;
+            framebuffer.buffer[row..row + tile.width]
+                .copy_from_slice(&self.colors[local_row..local_row + tile.width]);
+            framebuffer.zbuffer[row..row + tile.width]
+                .copy_from_slice(&self.depths[local_row..local_row + tile.width]);
+        }
+    }
+}
+
+// Sorts every screen-space triangle into the tiles its bounding box touches
+fn bin_triangles(width: usize, height: usize, triangles: &[[Vertex; 3]]) -> Vec<Tile> {
+    let tiles_x = width.div_ceil(TILE_SIZE);
+    let tiles_y = height.div_ceil(TILE_SIZE);
+
+    let mut tiles = Vec::with_capacity(tiles_x * tiles_y);
+    for ty in 0..tiles_y {
+        for tx in 0..tiles_x {
+            let x = tx * TILE_SIZE;
+            let y = ty * TILE_SIZE;
+            tiles.push(Tile {
+                x,
+                y,
+                width: TILE_SIZE.min(width - x),
+                height: TILE_SIZE.min(height - y),
+                triangles: Vec::new(),
+            });
+        }
+    }
+
+    for (index, tri) in triangles.iter().enumerate() {
+        let (min_x, min_y, max_x, max_y) = calculate_bounding_box(
+            &tri[0].transformed_position,
+            &tri[1].transformed_position,
+            &tri[2].transformed_position,
+        );
+        if max_x < 0 || max_y < 0 || min_x >= width as i32 || min_y >= height as i32 {
+            continue;
+        }
+        let first_tx = min_x.max(0) as usize / TILE_SIZE;
+        let first_ty = min_y.max(0) as usize / TILE_SIZE;
+        let last_tx = (max_x.max(0) as usize / TILE_SIZE).min(tiles_x - 1);
+        let last_ty = (max_y.max(0) as usize / TILE_SIZE).min(tiles_y - 1);
+        for ty in first_ty..=last_ty {
+            for tx in first_tx..=last_tx {
+                tiles[ty * tiles_x + tx].triangles.push(index);
+            }
+        }
+    }
+
+    tiles
+}
+
+// Rasterizes and shades the triangles tile by tile in parallel, writing the
+// results into the framebuffer. `shade` returns None to discard a fragment.
+pub fn rasterize<F>(
+    framebuffer: &mut Framebuffer,
+    triangles: &[[Vertex; 3]],
+    perspective_correct: bool,
+    blend_mode: BlendMode,
+    shade: F,
+) where
+    F: Fn(&Fragment) -> Option<Color> + Sync,
+{
+    let tiles = bin_triangles(framebuffer.width, framebuffer.height, triangles);
+    let target: &Framebuffer = framebuffer;
+
+    let shaded: Vec<(Tile, TileBuffer)> = tiles
+        .into_par_iter()
+        .filter(|tile| !tile.triangles.is_empty())
+        .map(|tile| {
+            let mut buffer = TileBuffer::read(target, &tile);
+            for &index in &tile.triangles {
+                let tri = &triangles[index];
+                triangle_fill(
+                    &tri[0],
+                    &tri[1],
+                    &tri[2],
+                    tile.clip_rect(),
+                    perspective_correct,
+                    |fragment| {
+                        let local_x = fragment.position.x as usize - tile.x;
+                        let local_y = fragment.position.y as usize - tile.y;
+                        let local_index = local_y * tile.width + local_x;
+                        // Early depth test so hidden fragments are never shaded
+                        if buffer.depths[local_index] > fragment.depth {
+                            if let Some(color) = shade(&fragment) {
+                                buffer.merge(local_index, color, fragment.depth, blend_mode);
+                            }
+                        }
+                    },
+                );
+            }
+            (tile, buffer)
+        })
+        .collect();
+
+    for (tile, buffer) in shaded {
+        buffer.write(framebuffer, &tile);
+    }
+}
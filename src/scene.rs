@@ -0,0 +1,902 @@
+use nalgebra_glm::{look_at, perspective, DVec3, Mat4, Vec3, Vec4};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use crate::camera::Camera;
+use crate::celestial_bodies::CelestialBody;
+use crate::collision::{bounce, deepest_contact, Collision, CollisionResponse};
+use crate::ephemeris::{mean_elements, EphemerisSettings};
+use crate::flight::{Controls, FlightSettings, Ship};
+use crate::gravity::{Drift, NBody, Particle};
+use crate::loaders::assets::{AssetManager, BodyHandles, Surface};
+use crate::loaders::error::LoadError;
+use crate::loaders::scene_file::SceneDescription;
+use crate::model::Material;
+use crate::rasterizer::BlendMode;
+use crate::scene_graph::SceneGraph;
+use crate::screen::framebuffer::Framebuffer;
+use crate::uniforms::{self, Light, RenderOptions, Uniforms};
+
+// The window shows the framebuffer stretched to 1.5 times its height and the
+// projection is built from the window size. Offscreen renders keep that ratio
+// so they match what the window displays.
+pub const WINDOW_STRETCH: f32 = 1.5;
+
+// Background stars are seeded so every run, windowed or headless, looks the same
+const STAR_SEED: u64 = 0x5747;
+
+// Everything needed to simulate and draw the solar system, shared by the
+// window loop and the headless renderer
+pub struct Scene {
+    pub uniform_array: Vec<Uniforms>,
+    // Scene file the bodies and meshes were built from, compared against on reload
+    description: SceneDescription,
+    // One node per body, in uniform_array order
+    graph: SceneGraph,
+    // Set while the gravity mode runs, it moves the simulated bodies instead of their orbits
+    gravity: Option<Gravity>,
+    // Set while the ephemeris mode runs, the planets of the ephemeris table follow the calendar
+    ephemeris: Option<EphemerisSettings>,
+    // World velocity of every body over the last advance, what the ship hits surfaces with
+    velocities: Vec<Vec3>,
+    assets: Arc<AssetManager>,
+    stars: Vec<(usize, usize)>,
+    projection_matrix: Mat4,
+    viewport_matrix: Mat4,
+}
+
+impl Scene {
+    pub fn new(
+        description: &SceneDescription,
+        framebuffer_width: usize,
+        framebuffer_height: usize,
+        projection_matrix: Mat4,
+    ) -> Self {
+        let solar_system = &description.bodies;
+
+        // Meshes, textures and normal maps, shared between bodies using the same files
+        let mut assets = AssetManager::new();
+        let handles: Vec<BodyHandles> = solar_system
+            .iter()
+            .map(|body| load_body_assets(&mut assets, description, body))
+            .collect();
+        let assets = Arc::new(assets);
+
+        // Uniform Array construction
+        let viewport_matrix =
+            create_viewport_matrix(framebuffer_width as f32, framebuffer_height as f32);
+        let uniform_array = solar_system
+            .iter()
+            .zip(handles)
+            .map(|(body, handles)| {
+                new_uniforms(body, &assets, handles, projection_matrix, viewport_matrix)
+            })
+            .collect();
+
+        // Star Array
+        let stars = generate_stars(framebuffer_width, framebuffer_height, 0.005, STAR_SEED);
+
+        let mut scene = Scene {
+            uniform_array,
+            description: description.clone(),
+            graph: SceneGraph::new(&find_parents(solar_system)),
+            gravity: None,
+            ephemeris: description.ephemeris.enabled.then_some(description.ephemeris),
+            velocities: vec![Vec3::zeros(); solar_system.len()],
+            assets,
+            stars,
+            projection_matrix,
+            viewport_matrix,
+        };
+        scene.apply_ephemeris();
+        scene.place_bodies();
+        if description.gravity.enabled {
+            scene.start_gravity(None);
+        }
+        scene
+    }
+
+    // Swaps in an edited scene description without restarting the simulation.
+    // Only files not loaded yet are read, every body keeps the current
+    // simulation time, and bodies without an orbit (the ship) stay where they
+    // were moved to unless the file moved them.
+    pub fn reload(&mut self, description: &SceneDescription) {
+        // Frames being drawn keep the manager they started with
+        let mut assets = AssetManager::clone(&self.assets);
+        let handles: Vec<BodyHandles> = description
+            .bodies
+            .iter()
+            .map(|body| load_body_assets(&mut assets, description, body))
+            .collect();
+        self.assets = Arc::new(assets);
+
+        let time = self.time();
+        let mut uniform_array = Vec::with_capacity(description.bodies.len());
+        for (body, handles) in description.bodies.iter().zip(handles) {
+            let mut uniform = new_uniforms(
+                body,
+                &self.assets,
+                handles,
+                self.projection_matrix,
+                self.viewport_matrix,
+            );
+            uniform.time = time;
+            let previous = self.description.bodies.iter().find(|old| old.id == body.id);
+            let current = self.uniform_array.iter().find(|old| old.celestial_body.id == body.id);
+            if let (Some(previous), Some(current)) = (previous, current) {
+                let moved_in_file = previous.position != body.position
+                    || previous.rotation != body.rotation;
+                if body.orbit.is_none() && !moved_in_file {
+                    let moved = &mut uniform.celestial_body;
+                    moved.position = current.celestial_body.position;
+                    moved.rotation = current.celestial_body.rotation;
+                }
+            }
+            uniform_array.push(uniform);
+        }
+
+        // A date set from the command line lasts until the file changes its [ephemeris] table
+        if description.ephemeris != self.description.ephemeris {
+            self.ephemeris = description.ephemeris.enabled.then_some(description.ephemeris);
+        }
+
+        // Simulated bodies carry on from their current state
+        let previous = self.gravity.take();
+        self.uniform_array = uniform_array;
+        self.velocities = vec![Vec3::zeros(); description.bodies.len()];
+        self.graph = SceneGraph::new(&find_parents(&description.bodies));
+        self.description = description.clone();
+        self.apply_ephemeris();
+        self.place_bodies();
+        if description.gravity.enabled {
+            self.start_gravity(previous.as_ref());
+        }
+    }
+
+    // Loads every mesh, texture and normal map read from `path` again
+    pub fn reload_file(&mut self, path: &str) -> Result<(), LoadError> {
+        let mut assets = AssetManager::clone(&self.assets);
+        assets.reload(path)?;
+        // A reloaded model can bring different materials, uniform_array follows the description order
+        for (uniform, body) in self.uniform_array.iter_mut().zip(&self.description.bodies) {
+            uniform.handles = load_body_assets(&mut assets, &self.description, body);
+        }
+        self.assets = Arc::new(assets);
+        Ok(())
+    }
+
+    pub fn assets(&self) -> &AssetManager {
+        &self.assets
+    }
+
+    // Every mesh and image file the scene uses
+    pub fn asset_paths(&self) -> Vec<String> {
+        self.assets.paths()
+    }
+
+    // Advances the simulation by one step
+    pub fn tick(&mut self) {
+        self.advance(1.0);
+    }
+
+    // Moves the simulation `ticks` forward, or back when negative
+    pub fn advance(&mut self, ticks: f64) {
+        let before: Vec<Vec3> = self.uniform_array.iter().map(|uniform| uniform.celestial_body.translation).collect();
+        for uniform in self.uniform_array.iter_mut() {
+            uniform.time += ticks;
+        }
+        if let Some(gravity) = &mut self.gravity {
+            gravity.simulation.advance(ticks);
+        }
+        self.place_bodies();
+        for ((velocity, uniform), before) in self.velocities.iter_mut().zip(&self.uniform_array).zip(before) {
+            *velocity = if ticks == 0.0 {
+                Vec3::zeros()
+            } else {
+                (uniform.celestial_body.translation - before) / ticks as f32
+            };
+        }
+    }
+
+    pub fn time(&self) -> f64 {
+        self.uniform_array.first().map_or(0.0, |uniform| uniform.time)
+    }
+
+    // Switches between the orbits of the scene file and the gravity mode. The
+    // simulation starts from where the bodies are now, turning it off puts
+    // them back on their orbits.
+    pub fn set_gravity(&mut self, enabled: bool) {
+        if enabled != self.gravity.is_some() {
+            self.replace_bodies(enabled);
+        }
+    }
+
+    // Places every body on its orbit again, and hands them to a new gravity
+    // simulation from there when `gravity` is set
+    fn replace_bodies(&mut self, gravity: bool) {
+        self.gravity = None;
+        self.graph = SceneGraph::new(&find_parents(&self.description.bodies));
+        self.place_bodies();
+        if gravity {
+            self.start_gravity(None);
+        }
+    }
+
+    // Turns on the ephemeris mode with the clock showing `julian_date` now
+    pub fn set_date(&mut self, julian_date: f64) {
+        let days_per_tick = self.description.ephemeris.days_per_tick;
+        self.ephemeris = Some(EphemerisSettings {
+            enabled: true,
+            start: julian_date - self.time() * days_per_tick,
+            days_per_tick,
+        });
+        self.apply_ephemeris();
+        self.replace_bodies(self.gravity.is_some());
+    }
+
+    // Date the simulation clock shows in the ephemeris mode, None without it
+    pub fn julian_date(&self) -> Option<f64> {
+        self.ephemeris
+            .map(|ephemeris| ephemeris.start + self.time() * ephemeris.days_per_tick)
+    }
+
+    // Swaps the orbits of the bodies found in the ephemeris table for their
+    // real ones, keeping the scene's distances, or puts the scene file's back
+    // without the mode
+    fn apply_ephemeris(&mut self) {
+        for (uniform, body) in self.uniform_array.iter_mut().zip(&self.description.bodies) {
+            uniform.celestial_body.orbit = match (self.ephemeris, body.orbit, mean_elements(&body.id)) {
+                (Some(ephemeris), Some(orbit), Some(elements)) => Some(elements.orbit_at(
+                    ephemeris.start,
+                    ephemeris.days_per_tick,
+                    orbit.semi_major_axis,
+                )),
+                _ => body.orbit,
+            };
+        }
+    }
+
+    pub fn gravity_enabled(&self) -> bool {
+        self.gravity.is_some()
+    }
+
+    // Energy and momentum change since the gravity mode started, None without it
+    pub fn gravity_drift(&self) -> Option<Drift> {
+        self.gravity.as_ref().map(|gravity| gravity.simulation.drift())
+    }
+
+    // Hands every body with a mass or a velocity to the N-body
+    // simulation. Bodies found in `previous` keep their state, the others
+    // start where they are placed now with the velocity of their orbit
+    // around their parent's simulated motion.
+    fn start_gravity(&mut self, previous: Option<&Gravity>) {
+        let settings = &self.description.gravity;
+        let parents = find_parents(&self.description.bodies);
+        let mut particles: Vec<Particle> = Vec::new();
+        let mut indices: Vec<Option<usize>> = Vec::with_capacity(self.uniform_array.len());
+        for (index, uniform) in self.uniform_array.iter().enumerate() {
+            let body = &uniform.celestial_body;
+            if !is_simulated(body) {
+                indices.push(None);
+                continue;
+            }
+            let mass = body.mass as f64;
+            let carried = previous.and_then(|previous| previous.particle(&body.id));
+            let particle = match carried {
+                Some(particle) => Particle { mass, ..particle },
+                None => {
+                    // Where the parent is, how fast it moves, and where it was placed kinematically
+                    let (parent_position, parent_velocity, parent_translation, parent_mass) =
+                        match parents[index] {
+                            Some(parent) => {
+                                let placed = self.uniform_array[parent].celestial_body.translation;
+                                let parent_mass = self.uniform_array[parent].celestial_body.mass;
+                                match indices[parent] {
+                                    Some(simulated) => {
+                                        let state = &particles[simulated];
+                                        (state.position, state.velocity, placed, parent_mass)
+                                    }
+                                    None => (nalgebra_glm::convert(placed), DVec3::zeros(), placed, parent_mass),
+                                }
+                            }
+                            None => (DVec3::zeros(), DVec3::zeros(), Vec3::zeros(), 0.0),
+                        };
+                    let velocity = match body.orbit {
+                        Some(orbit) => {
+                            let mu = settings.gravitational_constant as f32 * (parent_mass + body.mass);
+                            orbit.velocity(uniform.time as f32, mu)
+                        }
+                        None => body.velocity.unwrap_or_else(Vec3::zeros),
+                    };
+                    // Offsets are in the parent's tilted frame
+                    let velocity = match parents[index] {
+                        Some(parent) => (self.graph.world(parent) * velocity.push(0.0)).xyz(),
+                        None => velocity,
+                    };
+                    let offset: DVec3 = nalgebra_glm::convert(body.translation - parent_translation);
+                    Particle {
+                        mass,
+                        position: parent_position + offset,
+                        velocity: parent_velocity + nalgebra_glm::convert::<Vec3, DVec3>(velocity),
+                    }
+                }
+            };
+            indices.push(Some(particles.len()));
+            particles.push(particle);
+        }
+
+        let ids = self.description.bodies.iter().map(|body| body.id.clone()).collect();
+        self.gravity = Some(Gravity {
+            simulation: NBody::new(particles, settings),
+            indices,
+            ids,
+        });
+        // Simulated bodies move on their own, they no longer follow their parent
+        let graph_parents: Vec<Option<usize>> = parents
+            .iter()
+            .zip(&self.uniform_array)
+            .map(|(&parent, uniform)| parent.filter(|_| !is_simulated(&uniform.celestial_body)))
+            .collect();
+        self.graph = SceneGraph::new(&graph_parents);
+        self.place_bodies();
+    }
+
+    // Moves every body to where its orbit puts it at its current time,
+    // relative to wherever its parent is now
+    fn place_bodies(&mut self) {
+        for (index, uniform) in self.uniform_array.iter().enumerate() {
+            let simulated = self
+                .gravity
+                .as_ref()
+                .and_then(|gravity| gravity.indices[index].map(|particle| gravity.simulation.position(particle)));
+            let local = match simulated {
+                Some(position) => uniform.transform_at(&position),
+                None => uniform.local_transform(),
+            };
+            self.graph.set_local(index, local);
+        }
+        self.graph.update();
+        for (index, uniform) in self.uniform_array.iter_mut().enumerate() {
+            uniform.place(self.graph.world(index));
+        }
+    }
+
+    // Flies the body at `index` as the ship for `ticks`, with the [flight]
+    // settings. Every other body with a mass pulls on it in realistic mode,
+    // with the gravity mode's constant. It is placed on the next advance.
+    pub fn fly(&mut self, index: usize, ship: &mut Ship, controls: &Controls, ticks: f64) {
+        let gravitational_constant = self.description.gravity.gravitational_constant as f32;
+        let attractors: Vec<(Vec3, f32)> = self
+            .uniform_array
+            .iter()
+            .enumerate()
+            .filter(|(other, uniform)| *other != index && uniform.celestial_body.mass > 0.0)
+            .map(|(_, uniform)| (uniform.celestial_body.translation, gravitational_constant * uniform.celestial_body.mass))
+            .collect();
+        let settings = self.description.flight;
+        ship.fly(&mut self.uniform_array[index].celestial_body, controls, &settings, &attractors, ticks as f32);
+    }
+
+    // Pushes the ship at `index` out of the body it overlaps the most and
+    // answers with the [collisions] response. Every body is a sphere as big as
+    // its scale around its world position, the ship included, unless it is set
+    // not to collide.
+    pub fn collide(&mut self, index: usize, ship: &mut Ship) -> Option<Collision> {
+        let settings = self.description.collisions;
+        if !settings.enabled {
+            return None;
+        }
+        let center = self.uniform_array[index].celestial_body.translation;
+        let radius = self.uniform_array[index].celestial_body.scale;
+        let spheres = self
+            .uniform_array
+            .iter()
+            .enumerate()
+            .filter(|(other, uniform)| *other != index && uniform.celestial_body.collides)
+            .map(|(other, uniform)| (other, uniform.celestial_body.translation, uniform.celestial_body.scale));
+        let contact = deepest_contact(center, radius, spheres)?;
+        let surface_velocity = self.velocities[contact.index];
+        let speed = (surface_velocity - ship.velocity).dot(&contact.normal).max(0.0);
+
+        let body = &mut self.uniform_array[index].celestial_body;
+        match settings.response {
+            CollisionResponse::Respawn => {
+                let start = &self.description.bodies[index];
+                body.translation += start.position - body.position;
+                body.position = start.position;
+                body.rotation = start.rotation;
+                ship.stop();
+            }
+            CollisionResponse::Bounce | CollisionResponse::Stop => {
+                let push = contact.normal * contact.depth;
+                body.position += push;
+                body.translation += push;
+                if settings.response == CollisionResponse::Bounce {
+                    ship.velocity = bounce(ship.velocity, surface_velocity, contact.normal, settings.restitution);
+                } else {
+                    ship.velocity = surface_velocity;
+                    ship.turn_rate = 0.0;
+                }
+            }
+        }
+        self.place_bodies();
+        Some(Collision {
+            body: self.uniform_array[contact.index].celestial_body.id.clone(),
+            response: settings.response,
+            speed,
+        })
+    }
+
+    pub fn flight_settings(&self) -> &FlightSettings {
+        &self.description.flight
+    }
+
+    pub fn find(&self, id: &str) -> Option<usize> {
+        self.uniform_array
+            .iter()
+            .position(|uniform| uniform.celestial_body.id == id)
+    }
+
+    pub fn render(&mut self, framebuffer: &mut Framebuffer, camera: &Camera, render_options: RenderOptions) {
+        // Star printing
+        framebuffer.set_current_color(0xffffff);
+        for star in &self.stars {
+            framebuffer.point(star.0, star.1, 100.0);
+        }
+
+        // Lighting follows the sun wherever it is in the scene
+        let mut light = Light::default();
+        if let Some(sun) = self.find("sun") {
+            light.position = self.uniform_array[sun].celestial_body.translation;
+        }
+        let view_matrix = create_view_matrix(&camera.eye, &camera.center, &camera.up);
+        for uniform in self.uniform_array.iter_mut() {
+            uniform.render_options = render_options;
+            uniform.set_view_matrix(&view_matrix);
+            uniform.light = light;
+            uniform.camera_position = camera.eye;
+            uniform.assets = Arc::clone(&self.assets);
+        }
+
+        // Every model part is drawn on its own, its material decides the pass
+        let mut opaque = Vec::new();
+        let mut transparent = Vec::new();
+        for uniform in &self.uniform_array {
+            let is_in_view = is_in_view(
+                &uniform.celestial_body.translation,
+                &uniform.view_matrix,
+                &uniform.projection_matrix,
+            );
+            if !is_in_view {
+                continue;
+            }
+            let model = self.assets.model(uniform.handles.model);
+            for (part, surface) in model.parts.iter().zip(&uniform.handles.surfaces) {
+                match surface.blend_mode {
+                    BlendMode::Opaque => opaque.push((uniform, part, surface)),
+                    BlendMode::Alpha => transparent.push((uniform, part, surface)),
+                }
+            }
+        }
+        // Transparent parts go last, farthest body first, so they blend over everything behind them
+        transparent.sort_by(|(a, ..), (b, ..)| {
+            let distance_a = (a.celestial_body.translation - camera.eye).magnitude();
+            let distance_b = (b.celestial_body.translation - camera.eye).magnitude();
+            distance_b.total_cmp(&distance_a)
+        });
+
+        for (uniform, part, surface) in opaque.into_iter().chain(transparent) {
+            uniforms::render(framebuffer, uniform, &part.mesh, surface);
+        }
+    }
+}
+
+fn load_body_assets(
+    assets: &mut AssetManager,
+    description: &SceneDescription,
+    body: &CelestialBody,
+) -> BodyHandles {
+    let mesh = &description.meshes[&body.mesh];
+    let model = assets.load_model_or_fallback(&mesh.path, mesh.options, &body.id);
+    let texture = body
+        .texture_path
+        .as_ref()
+        .map(|path| assets.load_texture_or_fallback(path, &body.id));
+    let normal_map = body
+        .normalmap_path
+        .as_ref()
+        .map(|path| assets.load_normal_map_or_fallback(path, &body.id));
+
+    let parts: Vec<(String, Material)> = assets
+        .model(model)
+        .parts
+        .iter()
+        .map(|part| (part.name.clone(), part.material.clone()))
+        .collect();
+    let surfaces = parts
+        .iter()
+        .map(|(name, material)| {
+            // Maps of the material are reported with the part that uses them
+            let user = format!("{}/{name}", body.id);
+            Surface {
+                texture: match &material.diffuse_map {
+                    Some(path) => Some(assets.load_texture_or_fallback(path, &user)),
+                    None => texture,
+                },
+                normal_map: match &material.normal_map {
+                    Some(path) => Some(assets.load_normal_map_or_fallback(path, &user)),
+                    None => normal_map,
+                },
+                diffuse: material.diffuse,
+                shininess: material.shininess.unwrap_or(body.shininess),
+                specular_strength: material.specular_strength.unwrap_or(body.specular_strength),
+                // Materials with a dissolve below one need blending even on opaque bodies
+                blend_mode: if material.diffuse.a < 255 {
+                    BlendMode::Alpha
+                } else {
+                    body.blend_mode
+                },
+            }
+        })
+        .collect();
+
+    BodyHandles { model, surfaces }
+}
+
+fn new_uniforms(
+    body: &CelestialBody,
+    assets: &Arc<AssetManager>,
+    handles: BodyHandles,
+    projection_matrix: Mat4,
+    viewport_matrix: Mat4,
+) -> Uniforms {
+    Uniforms {
+        model_matrix: create_model_matrix(&body.translation, &body.scale, &body.rotation),
+        view_matrix: Mat4::identity(),
+        projection_matrix,
+        viewport_matrix,
+        time: 0.0,
+        celestial_body: body.clone(),
+        render_options: RenderOptions::default(),
+        light: Light::default(),
+        camera_position: Vec3::zeros(),
+        assets: Arc::clone(assets),
+        handles,
+    }
+}
+
+// N-body simulation of the scene bodies
+struct Gravity {
+    simulation: NBody,
+    // Particle of each body in uniform_array, None for the ones left to the scene graph
+    indices: Vec<Option<usize>>,
+    // Body ids in uniform_array order, to find particles again after a reload
+    ids: Vec<String>,
+}
+
+impl Gravity {
+    fn particle(&self, id: &str) -> Option<Particle> {
+        let index = self.ids.iter().position(|other| other == id)?;
+        self.indices[index].map(|particle| self.simulation.particles()[particle])
+    }
+}
+
+// Massless bodies without a starting velocity (the ship, rings, a moon too
+// close to hold with its planet's real mass) keep following their parent
+fn is_simulated(body: &CelestialBody) -> bool {
+    body.mass > 0.0 || body.velocity.is_some()
+}
+
+fn find_parents(bodies: &[CelestialBody]) -> Vec<Option<usize>> {
+    bodies
+        .iter()
+        .map(|body| {
+            body.parent
+                .as_ref()
+                .and_then(|parent| bodies.iter().position(|other| &other.id == parent))
+        })
+        .collect()
+}
+
+fn is_in_view(position: &Vec3, view_matrix: &Mat4, projection_matrix: &Mat4) -> bool {
+  let pos4 = Vec4::new(position.x, position.y, position.z, 1.0);
+
+  // Transform to clip space
+  let view_position = view_matrix * pos4;
+  let clip_position = projection_matrix * view_position;
+
+  // Perform the homogeneous division
+  if clip_position.w == 0.0 {
+      return false; // Avoid division by zero
+  }
+
+  let ndc_position = clip_position / clip_position.w;
+
+  // Check if it's within the normalized device coordinates range
+  ndc_position.x >= -1.0
+      && ndc_position.x <= 1.0
+      && ndc_position.y >= -1.0
+      && ndc_position.y <= 1.0
+      && ndc_position.z >= -1.0
+      && ndc_position.z <= 1.0
+}
+
+fn generate_stars(width: usize, height: usize, density: f32, seed: u64) -> Vec<(usize, usize)> {
+    // Calculate the total number of stars based on the density
+    let total_area = (width * height) as f32;
+    let number_of_stars = (density * total_area) as usize;
+
+    // Initialize a seeded random number generator
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    // Generate random positions for each star
+    let mut stars = Vec::with_capacity(number_of_stars);
+    for _ in 0..number_of_stars {
+        let x = rng.gen_range(0..width);
+        let y = rng.gen_range(0..height);
+        stars.push((x, y));
+    }
+
+    stars
+}
+
+// Matrix generation
+pub fn create_model_matrix(translation: &Vec3, scale: &f32, rotation: &Vec3) -> Mat4 {
+    let transform_matrix = Mat4::new(
+        *scale,
+        0.0,
+        0.0,
+        translation.x,
+        0.0,
+        *scale,
+        0.0,
+        translation.y,
+        0.0,
+        0.0,
+        *scale,
+        translation.z,
+        0.0,
+        0.0,
+        0.0,
+        1.0,
+    );
+
+    transform_matrix * create_rotation_matrix(rotation)
+}
+
+// Euler angles in radians, applied around x, then y, then z
+pub fn create_rotation_matrix(rotation: &Vec3) -> Mat4 {
+    let (sin_x, cos_x) = rotation.x.sin_cos();
+    let (sin_y, cos_y) = rotation.y.sin_cos();
+    let (sin_z, cos_z) = rotation.z.sin_cos();
+
+    let rotation_matrix_x = Mat4::new(
+        1.0, 0.0, 0.0, 0.0, 0.0, cos_x, -sin_x, 0.0, 0.0, sin_x, cos_x, 0.0, 0.0, 0.0, 0.0, 1.0,
+    );
+    let rotation_matrix_y = Mat4::new(
+        cos_y, 0.0, sin_y, 0.0, 0.0, 1.0, 0.0, 0.0, -sin_y, 0.0, cos_y, 0.0, 0.0, 0.0, 0.0, 1.0,
+    );
+    let rotation_matrix_z = Mat4::new(
+        cos_z, -sin_z, 0.0, 0.0, sin_z, cos_z, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+    );
+
+    rotation_matrix_z * rotation_matrix_y * rotation_matrix_x
+}
+
+pub fn create_view_matrix(eye: &Vec3, center: &Vec3, up: &Vec3) -> Mat4 {
+    look_at(eye, center, up)
+}
+
+pub fn create_perspective_matrix(window_width: f32, window_height: f32) -> Mat4 {
+    let fov = 45.0 * PI / 180.0;
+    let aspect_ratio = window_width / window_height;
+    let near = 0.1;
+    let far = 1000.0;
+
+    perspective(fov, aspect_ratio, near, far)
+}
+
+pub fn create_viewport_matrix(width: f32, height: f32) -> Mat4 {
+    Mat4::new(
+        width / 2.0,
+        0.0,
+        0.0,
+        width / 2.0,
+        0.0,
+        -height / 2.0,
+        0.0,
+        height / 2.0,
+        0.0,
+        0.0,
+        1.0,
+        0.0,
+        0.0,
+        0.0,
+        0.0,
+        1.0,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loaders::scene_file::{load_scene, DEFAULT_SCENE};
+
+    #[test]
+    fn reload_keeps_time_and_ship_position() {
+        let description = load_scene(DEFAULT_SCENE).unwrap();
+        let mut scene = Scene::new(&description, 80, 60, create_perspective_matrix(80.0, 90.0));
+        for _ in 0..10 {
+            scene.tick();
+        }
+        let ship = scene.find("spaceship").unwrap();
+        let body = &mut scene.uniform_array[ship].celestial_body;
+        body.position.z += 2.0;
+        body.translation.z += 2.0;
+        let earth_position = scene.uniform_array[scene.find("earth").unwrap()].celestial_body.translation;
+
+        // Earth grows and Mercury is dropped, which shifts every later body in the array
+        let mut edited = description.clone();
+        edited.bodies.retain(|body| body.id != "mercury");
+        edited.bodies.iter_mut().find(|body| body.id == "earth").unwrap().scale = 0.9;
+        scene.reload(&edited);
+
+        let earth = &scene.uniform_array[scene.find("earth").unwrap()];
+        assert_eq!(earth.time, 10.0);
+        assert_eq!(earth.celestial_body.scale, 0.9);
+        assert_eq!(earth.celestial_body.translation, earth_position);
+        let ship = &scene.uniform_array[scene.find("spaceship").unwrap()];
+        assert_eq!(ship.celestial_body.translation, Vec3::new(30.0, 0.0, 2.0));
+        assert!(scene.find("mercury").is_none());
+    }
+
+    #[test]
+    fn the_ship_falls_towards_the_sun_in_realistic_mode() {
+        let description = load_scene(DEFAULT_SCENE).unwrap();
+        let mut scene = Scene::new(&description, 80, 60, create_perspective_matrix(80.0, 90.0));
+        let index = scene.find("spaceship").unwrap();
+        let start = scene.uniform_array[index].celestial_body.translation;
+        let mut ship = Ship::new(scene.flight_settings());
+        ship.toggle_mode();
+        for _ in 0..10 {
+            scene.fly(index, &mut ship, &Controls::default(), 1.0);
+            scene.tick();
+        }
+        let position = scene.uniform_array[index].celestial_body.translation;
+        assert!(position.norm() < start.norm());
+        // Pulled by the sun far more than by anything else
+        assert!(position.normalize().dot(&start.normalize()) > 0.999);
+        assert!(ship.velocity.dot(&start) < 0.0);
+    }
+
+    #[test]
+    fn the_ship_bounces_lands_or_respawns_when_it_hits_a_planet() {
+        let mut description = load_scene(DEFAULT_SCENE).unwrap();
+        let mut scene = Scene::new(&description, 80, 60, create_perspective_matrix(80.0, 90.0));
+        let index = scene.find("spaceship").unwrap();
+        let earth = scene.uniform_array[scene.find("earth").unwrap()].celestial_body.clone();
+        let start = scene.uniform_array[index].celestial_body.translation;
+        let mut ship = Ship::new(scene.flight_settings());
+        assert!(scene.collide(index, &mut ship).is_none());
+
+        // Just inside the earth's sphere on its +y side, diving into it
+        let dive = |scene: &mut Scene, ship: &mut Ship| {
+            let body = &mut scene.uniform_array[index].celestial_body;
+            body.position = earth.translation + Vec3::new(0.0, earth.scale, 0.0);
+            body.translation = body.position;
+            ship.velocity = Vec3::new(0.0, -0.1, 0.0);
+            scene.collide(index, ship).unwrap()
+        };
+        let surface = earth.scale + scene.uniform_array[index].celestial_body.scale;
+        let collision = dive(&mut scene, &mut ship);
+        assert_eq!(collision.to_string(), "Bounced off earth at 0.100");
+        assert!((ship.velocity.y - 0.05).abs() < 1e-6);
+        let translation = scene.uniform_array[index].celestial_body.translation;
+        assert!(((translation - earth.translation).norm() - surface).abs() < 1e-4);
+
+        description.collisions.response = CollisionResponse::Stop;
+        scene.reload(&description);
+        assert_eq!(dive(&mut scene, &mut ship).response, CollisionResponse::Stop);
+        assert_eq!(ship.velocity, Vec3::zeros());
+
+        description.collisions.response = CollisionResponse::Respawn;
+        scene.reload(&description);
+        assert_eq!(dive(&mut scene, &mut ship).to_string(), "Crashed into earth at 0.100");
+        assert_eq!(scene.uniform_array[index].celestial_body.translation, start);
+
+        // Above Saturn the flat rings are no obstacle, only the planet is
+        let saturn = scene.uniform_array[scene.find("saturn").unwrap()].celestial_body.clone();
+        let body = &mut scene.uniform_array[index].celestial_body;
+        body.position = saturn.translation + Vec3::new(0.0, 1.0, 0.0);
+        body.translation = body.position;
+        assert!(scene.collide(index, &mut ship).is_none());
+
+        description.collisions.enabled = false;
+        scene.reload(&description);
+        let body = &mut scene.uniform_array[index].celestial_body;
+        body.position = earth.translation;
+        body.translation = earth.translation;
+        assert!(scene.collide(index, &mut ship).is_none());
+    }
+
+    #[test]
+    fn children_move_with_their_parents() {
+        let description = load_scene(DEFAULT_SCENE).unwrap();
+        let mut scene = Scene::new(&description, 80, 60, create_perspective_matrix(80.0, 90.0));
+        let [earth, moon, saturn, rings] = ["earth", "moon", "saturn", "rings"].map(|id| scene.find(id).unwrap());
+        let orbit = scene.uniform_array[moon].celestial_body.orbit.unwrap();
+        for _ in 0..200 {
+            scene.tick();
+            let position = |index: usize| scene.uniform_array[index].celestial_body.translation;
+            let distance = (position(moon) - position(earth)).norm();
+            let (a, e) = (orbit.semi_major_axis, orbit.eccentricity);
+            assert!(distance > a * (1.0 - e) - 1e-3 && distance < a * (1.0 + e) + 1e-3, "{distance}");
+            assert!((position(rings) - position(saturn)).norm() < 1e-4);
+        }
+        // The rings keep their tilt while they spin
+        let rings_up = scene.uniform_array[rings].model_matrix * Vec4::new(0.0, 1.0, 0.0, 0.0);
+        assert!((rings_up.normalize().y - 0.3_f32.cos()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn gravity_mode_keeps_the_planets_on_their_orbits() {
+        let mut description = load_scene(DEFAULT_SCENE).unwrap();
+        description.gravity.enabled = true;
+        let mut scene = Scene::new(&description, 80, 60, create_perspective_matrix(80.0, 90.0));
+        let translation = |scene: &Scene, id: &str| scene.uniform_array[scene.find(id).unwrap()].celestial_body.translation;
+        // Starting where the orbits put them
+        let earth = description.bodies.iter().find(|body| body.id == "earth").unwrap().orbit.unwrap();
+        assert!((translation(&scene, "earth") - earth.position(0.0)).norm() < 1e-5);
+
+        for _ in 0..2000 {
+            scene.tick();
+        }
+        for planet in ["mercury", "venus", "earth", "mars", "jupiter", "saturn", "uranus", "pluto"] {
+            let orbit = description.bodies.iter().find(|body| body.id == planet).unwrap().orbit.unwrap();
+            let distance = (translation(&scene, planet) - translation(&scene, "sun")).norm();
+            let (a, e) = (orbit.semi_major_axis, orbit.eccentricity);
+            // Jupiter's pull stretches the neighbouring orbits a little
+            assert!(distance > 0.95 * a * (1.0 - e) && distance < 1.05 * a * (1.0 + e), "{planet}: {distance}");
+        }
+        // The massless moon keeps its orbit around the simulated earth
+        assert!((translation(&scene, "moon") - translation(&scene, "earth")).norm() < 1.3);
+        let drift = scene.gravity_drift().unwrap();
+        assert!(drift.energy < 1e-6 && drift.momentum < 1e-9, "{drift}");
+
+        // Turning it off puts the bodies back on their orbits
+        scene.set_gravity(false);
+        assert!(scene.gravity_drift().is_none());
+        assert!((translation(&scene, "earth") - earth.position(2000.0)).norm() < 1e-3);
+    }
+
+    #[test]
+    fn dates_put_the_planets_where_they_were() {
+        let description = load_scene(DEFAULT_SCENE).unwrap();
+        let mut scene = Scene::new(&description, 80, 60, create_perspective_matrix(80.0, 90.0));
+        assert!(scene.julian_date().is_none());
+        for _ in 0..5 {
+            scene.tick();
+        }
+        let date = crate::calendar::CalendarDate::parse("2020-10-13T23:00").unwrap().julian_date();
+        scene.set_date(date);
+        assert_eq!(scene.julian_date(), Some(date));
+
+        // Mars at opposition, in line with the sun and the earth
+        let translation = |scene: &Scene, id: &str| scene.uniform_array[scene.find(id).unwrap()].celestial_body.translation;
+        let (earth, mars) = (translation(&scene, "earth"), translation(&scene, "mars"));
+        let angle = (earth.x * mars.z - earth.z * mars.x).atan2(earth.x * mars.x + earth.z * mars.z);
+        assert!(angle.to_degrees().abs() < 1.0, "{}", angle.to_degrees());
+        // At the scene's own distance from the sun
+        let orbit = description.bodies[scene.find("mars").unwrap()].orbit.unwrap();
+        assert!((mars.norm() - orbit.semi_major_axis).abs() < orbit.semi_major_axis * orbit.eccentricity + 0.1);
+
+        // The clock keeps running, and an edit to the file keeps the date
+        for _ in 0..10 {
+            scene.tick();
+        }
+        scene.reload(&description);
+        assert_eq!(scene.julian_date(), Some(date + 10.0));
+    }
+}
@@ -0,0 +1,86 @@
+use nalgebra_glm::Mat4;
+
+struct Node {
+    parent: Option<usize>,
+    // Relative to the parent, or to the world for roots
+    local: Mat4,
+    world: Mat4,
+}
+
+// Transform hierarchy of the scene bodies. Nodes are stored with every parent
+// before its children, so world transforms are composed in one pass.
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+}
+
+impl SceneGraph {
+    // `parents[i]` is the index of node i's parent, which must be lower than i
+    pub fn new(parents: &[Option<usize>]) -> Self {
+        let nodes = parents
+            .iter()
+            .enumerate()
+            .map(|(index, &parent)| {
+                assert!(parent.is_none_or(|parent| parent < index), "node {index} comes before its parent");
+                Node {
+                    parent,
+                    local: Mat4::identity(),
+                    world: Mat4::identity(),
+                }
+            })
+            .collect();
+        SceneGraph { nodes }
+    }
+
+    pub fn set_local(&mut self, index: usize, local: Mat4) {
+        self.nodes[index].local = local;
+    }
+
+    // Recomputes every world transform from the local ones
+    pub fn update(&mut self) {
+        for index in 0..self.nodes.len() {
+            let parent_world = match self.nodes[index].parent {
+                Some(parent) => self.nodes[parent].world,
+                None => Mat4::identity(),
+            };
+            self.nodes[index].world = parent_world * self.nodes[index].local;
+        }
+    }
+
+    pub fn world(&self, index: usize) -> &Mat4 {
+        &self.nodes[index].world
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra_glm::{Vec3, Vec4};
+    use std::f32::consts::PI;
+
+    fn world_position(graph: &SceneGraph, index: usize) -> Vec3 {
+        (graph.world(index) * Vec4::new(0.0, 0.0, 0.0, 1.0)).xyz()
+    }
+
+    #[test]
+    fn children_follow_their_parents() {
+        // Sun, planet 10 units out turned a quarter around y, moon 2 units out from the planet
+        let mut graph = SceneGraph::new(&[None, Some(0), Some(1)]);
+        graph.set_local(0, Mat4::new_translation(&Vec3::new(1.0, 0.0, 0.0)));
+        graph.set_local(
+            1,
+            Mat4::new_translation(&Vec3::new(10.0, 0.0, 0.0)) * Mat4::from_axis_angle(&Vec3::y_axis(), PI / 2.0),
+        );
+        graph.set_local(2, Mat4::new_translation(&Vec3::new(2.0, 0.0, 0.0)));
+        graph.update();
+
+        assert!((world_position(&graph, 1) - Vec3::new(11.0, 0.0, 0.0)).norm() < 1e-5);
+        // The planet's turn swings the moon's offset from +x to -z
+        assert!((world_position(&graph, 2) - Vec3::new(11.0, 0.0, -2.0)).norm() < 1e-5);
+    }
+
+    #[test]
+    #[should_panic(expected = "comes before its parent")]
+    fn parents_come_first() {
+        SceneGraph::new(&[Some(1), None]);
+    }
+}
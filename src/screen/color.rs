@@ -3,21 +3,27 @@ pub struct Color {
     pub r: u8,
     pub g: u8,
     pub b: u8,
+    pub a: u8,
 }
 
 impl Color {
     pub fn new(r: i32, g: i32, b: i32) -> Self  {
+        Color::new_rgba(r, g, b, 255)
+    }
+    pub fn new_rgba(r: i32, g: i32, b: i32, a: i32) -> Self  {
         Color {
             r: (r as f32).clamp(0.0, 255.0) as u8,
             g: (g as f32).clamp(0.0, 255.0) as u8,
-            b: (b as f32).clamp(0.0, 255.0) as u8
+            b: (b as f32).clamp(0.0, 255.0) as u8,
+            a: (a as f32).clamp(0.0, 255.0) as u8
         }
     }
     pub fn black() -> Self {
         Color{
             r: 0x00,
             g: 0x00,
-            b: 0x00
+            b: 0x00,
+            a: 0xFF
         }
     }
 
@@ -25,12 +31,29 @@ impl Color {
         let r = (hex >> 16 & 0xFF) as u8;
         let g = (hex >> 8 & 0xFF) as u8;
         let b = (hex & 0xFF) as u8;
-        Color {r, g, b}
+        Color {r, g, b, a: 0xFF}
     }
 
-    pub fn to_hex(&self) -> u32 {
+    pub fn to_hex(self) -> u32 {
         ((self.r as u32)<< 16) | ((self.g as u32) << 8) | (self.b as u32)
     }
+
+    pub fn lerp(self, other: Color, t: f32) -> Color {
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as i32;
+        Color::new_rgba(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+            mix(self.a, other.a),
+        )
+    }
+
+    // Source-over compositing of self on top of an opaque destination
+    pub fn blend_over(self, destination: Color) -> Color {
+        let alpha = self.a as f32 / 255.0;
+        let result = destination.lerp(self, alpha);
+        Color { a: 0xFF, ..result }
+    }
 }
 
 impl std::ops::Add for Color{
@@ -40,10 +63,27 @@ impl std::ops::Add for Color{
         let r = self.r.saturating_add(other.r);
         let g = self.g.saturating_add(other.g);
         let b = self.b.saturating_add(other.b);
-        Color { r, g, b}
+        let a = self.a.saturating_add(other.a);
+        Color { r, g, b, a}
     }
 }
 
+// Channel-wise product, used to tint a texel with a material color
+impl std::ops::Mul for Color {
+    type Output = Color;
+
+    fn mul(self, other: Color) -> Color {
+        let mix = |a: u8, b: u8| ((a as u16 * b as u16 + 127) / 255) as u8;
+        Color {
+            r: mix(self.r, other.r),
+            g: mix(self.g, other.g),
+            b: mix(self.b, other.b),
+            a: mix(self.a, other.a),
+        }
+    }
+}
+
+// Scales the color channels, alpha is left untouched
 impl std::ops::Mul<f32> for Color {
     type Output = Color;
 
@@ -51,6 +91,6 @@ impl std::ops::Mul<f32> for Color {
         let r = (self.r as f32 * factor).clamp(0.0, 255.0) as u8;
         let g = (self.g as f32 * factor).clamp(0.0, 255.0) as u8;
         let b = (self.b as f32 * factor).clamp(0.0, 255.0) as u8;
-        Color { r, g, b}
+        Color { r, g, b, a: self.a}
     }
-}
\ No newline at end of file
+}
@@ -45,9 +45,14 @@ impl Framebuffer {
         }
     }
     pub fn color_array_to_u32(&mut self) -> Vec<u32> {
-        self.buffer.iter().map(|color| {
-            ((color.r as u32) << 16) | ((color.g as u32) << 8) | (color.b as u32)
-        }).collect()  // Collect into a Vec<u32>
+        self.buffer.iter().map(|color| color.to_hex()).collect()  // Collect into a Vec<u32>
+    }
+
+    pub fn save_png(&self, path: &str) -> image::ImageResult<()> {
+        let pixels = self.buffer.iter().flat_map(|color| [color.r, color.g, color.b]).collect();
+        let image = image::RgbImage::from_raw(self.width as u32, self.height as u32, pixels)
+            .expect("framebuffer size matches its buffer");
+        image.save(path)
     }
 
     // pub fn set_bgcolor(&mut self, color: u32){
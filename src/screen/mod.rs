@@ -1,2 +1,3 @@
 pub mod color;
-pub mod framebuffer;
\ No newline at end of file
+pub mod framebuffer;
+pub mod text;
\ No newline at end of file
@@ -1,9 +1,8 @@
 
 use nalgebra_glm::{dot, mat4_to_mat3, Mat3, Vec3, Vec4};
 use crate::fragments::Fragment;
-use crate::normal_map::{with_normal_map, NormalMap};
+use crate::loaders::assets::Surface;
 use crate::screen::color::Color;
-use crate::texture::{with_texture, Texture};
 use crate::uniforms::Uniforms;
 use crate::vertex::Vertex;
 
@@ -15,63 +14,92 @@ pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
     vertex.position.z,
     1.0
   );
-  let transformed = uniforms.projection_matrix * uniforms.view_matrix * uniforms.model_matrix * position;
-
-  // Perform perspective division
-  let w = transformed.w;  
-  let ndc_position = Vec4::new(
-    transformed.x / w,
-    transformed.y / w,
-    transformed.z / w,
-    1.0
-  );
-
-  // apply viewport matrix
-  let screen_position = uniforms.viewport_matrix * ndc_position;
+  let world_position = uniforms.model_matrix * position;
+  // Stay in homogeneous clip space, division happens after clipping
+  let clip_position = uniforms.projection_matrix * uniforms.view_matrix * world_position;
 
   // Transform normal
   let model_mat3 = mat4_to_mat3(&uniforms.model_matrix); 
   let normal_matrix = model_mat3.transpose().try_inverse().unwrap_or(Mat3::identity());
 
   let transformed_normal = normal_matrix * vertex.normal;
+  // Tangents live on the surface, so they follow the model matrix itself
+  let transformed_tangent = model_mat3 * vertex.tangent;
+  let transformed_bitangent = model_mat3 * vertex.bitangent;
 
   // Create a new Vertex with transformed attributes
   Vertex {
     position: vertex.position,
     normal: vertex.normal,
+    tangent: vertex.tangent,
+    bitangent: vertex.bitangent,
     tex_coords: vertex.tex_coords,
     color: vertex.color,
-    transformed_position: Vec3::new(screen_position.x, screen_position.y, screen_position.z),
+    world_position: world_position.xyz(),
+    clip_position,
+    inv_w: 1.0,
+    transformed_position: Vec3::zeros(),
     transformed_normal,
+    transformed_tangent,
+    transformed_bitangent,
   }
 }
 
-pub fn fragment_shader(fragment :&Fragment, uniforms: &Uniforms)->Color{
+pub fn fragment_shader(fragment :&Fragment, uniforms: &Uniforms, surface: &Surface)->Color{
   if uniforms.celestial_body.id!="sun"{
-    let intensity = calculate_lightning(fragment, uniforms);
-    let texture_color = get_fragment_texture(fragment, uniforms);
-    texture_color*(intensity.max(0.2).min(2.0))
+    let (diffuse, specular) = calculate_lightning(fragment, uniforms, surface);
+    let texture_color = get_fragment_texture(fragment, uniforms, surface);
+    let highlight = Color::new_rgba(255, 255, 255, 0) * specular;
+    texture_color*(uniforms.light.ambient + diffuse) + highlight
   } else{
-    let texture_color = get_fragment_texture(fragment, uniforms);
-    texture_color
+    get_fragment_texture(fragment, uniforms, surface)
   }
 }
 
-pub fn get_fragment_texture(fragment: &Fragment, uniforms: &Uniforms)->Color{
-  let bid = &uniforms.celestial_body.id;
-  let base_color = with_texture(bid,&|texture: &Texture|{
-    texture.sample(fragment.texture_pos.x, fragment.texture_pos.y)
-  });
-  base_color
+// Diffuse map tinted by the material color, or the plain color without a map
+pub fn get_fragment_texture(fragment: &Fragment, uniforms: &Uniforms, surface: &Surface)->Color{
+  let Some(handle) = surface.texture else {
+    return surface.diffuse;
+  };
+  let texture = uniforms.assets.texture(handle);
+  let filter = uniforms.render_options.filter_mode;
+  let lod = texture.level_of_detail(fragment.uv_dx, fragment.uv_dy);
+  texture.sample(fragment.texture_pos.x, fragment.texture_pos.y, lod, filter) * surface.diffuse
 }
 
-pub fn calculate_lightning(fragment:&Fragment, uniforms: &Uniforms)->f32{
-  let bid = &uniforms.celestial_body.id;
-  let normal_from_map = with_normal_map(bid,|normal_map: &NormalMap|{
-    normal_map.sample(fragment.texture_pos.x, fragment.texture_pos.y)
-  });
-  let modified_normal = (fragment.normal + normal_from_map).normalize();
-  let light_pos = Vec3::new(1.0, 1.0, 0.0);
+// Blinn-Phong terms for the point light, returned as (diffuse, specular)
+pub fn calculate_lightning(fragment:&Fragment, uniforms: &Uniforms, surface: &Surface)->(f32, f32){
+  let modified_normal = match surface.normal_map {
+    Some(handle) => {
+      let normal_map = uniforms.assets.normal_map(handle);
+      let filter = uniforms.render_options.filter_mode;
+      let lod = normal_map.level_of_detail(fragment.uv_dx, fragment.uv_dy);
+      let normal_from_map = normal_map.sample(fragment.texture_pos.x, fragment.texture_pos.y, lod, filter);
+      perturb_normal(fragment, &normal_from_map)
+    }
+    None => fragment.normal.normalize(),
+  };
 
-  dot(&modified_normal, &light_pos)
-}
\ No newline at end of file
+  let light_dir = (uniforms.light.position - fragment.world_position).normalize();
+  let view_dir = (uniforms.camera_position - fragment.world_position).normalize();
+  let halfway = (light_dir + view_dir).normalize();
+
+  let diffuse = dot(&modified_normal, &light_dir).max(0.0) * uniforms.light.intensity;
+  let specular = if diffuse > 0.0 {
+    dot(&modified_normal, &halfway).max(0.0).powf(surface.shininess) * surface.specular_strength * uniforms.light.intensity
+  } else {
+    0.0
+  };
+  (diffuse, specular)
+}
+
+// Moves a tangent-space normal map sample into world space through the TBN basis
+fn perturb_normal(fragment: &Fragment, normal_from_map: &Vec3) -> Vec3 {
+  let normal = fragment.normal;
+  // Interpolation skews the basis, re-orthogonalize before using it
+  let tangent = (fragment.tangent - normal * dot(&normal, &fragment.tangent)).normalize();
+  let handedness = if dot(&normal.cross(&tangent), &fragment.bitangent) < 0.0 { -1.0 } else { 1.0 };
+  let bitangent = normal.cross(&tangent) * handedness;
+  let tbn = Mat3::from_columns(&[tangent, bitangent, normal]);
+  (tbn * normal_from_map).normalize()
+}
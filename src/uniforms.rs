@@ -1,126 +1,140 @@
-use crate::fragments::{triangle_fill, Fragment};
-use crate::screen::color::Color;
+use crate::clipping::{clip_triangle, to_screen, triangulate, ClipMode};
+use crate::culling::{is_culled, signed_area};
+use crate::loaders::assets::{AssetManager, BodyHandles, Surface};
+use crate::loaders::mipmap::FilterMode;
+use crate::mesh::Mesh;
+use crate::rasterizer::rasterize;
 use crate::shader::fragment_shader;
 use crate::vertex::Vertex;
 use crate::CelestialBody;
 use crate::{screen::framebuffer::Framebuffer, shader::vertex_shader};
-use nalgebra_glm::{Mat4, Vec3};
+use crate::scene::create_rotation_matrix;
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+use rayon::prelude::*;
+use std::sync::Arc;
 pub struct Uniforms {
     pub model_matrix: Mat4,
     pub view_matrix: Mat4,
     pub projection_matrix: Mat4,
     pub viewport_matrix: Mat4,
-    pub time: u32,
+    // Simulation ticks, fractional and running backwards under the time controls
+    pub time: f64,
     pub celestial_body: CelestialBody,
-    pub looking_dir: Vec3
+    pub render_options: RenderOptions,
+    pub light: Light,
+    pub camera_position: Vec3,
+    pub assets: Arc<AssetManager>,
+    pub handles: BodyHandles,
 }
 
-impl Uniforms {
-    pub fn set_view_matrix(&mut self, view_matrix: &Mat4) {
-        self.view_matrix = *view_matrix;
-    }
-    pub fn increment_time(&mut self) {
-        self.time += 1;
-    }
-    pub fn orbit(&mut self) {
-        if self.celestial_body.year > 0.001 && self.celestial_body.day > 0.0 {
-            let orbit_speed = self.celestial_body.year * self.time as f32;
-            let self_rotation_speed =
-                (self.celestial_body.day - self.celestial_body.year) * self.time as f32; // Example for slower self-rotation
-
-            self.celestial_body.translation = Vec3::new(
-                self.celestial_body.orbit_radius * orbit_speed.cos(),
-                0.0,
-                self.celestial_body.orbit_radius * orbit_speed.sin(),
-            );
-            // Orbit around the origin
-
-            let self_rotation_matrix = Mat4::from_axis_angle(&Vec3::y_axis(), self_rotation_speed);
-            let translation_matrix = Mat4::new_translation(&self.celestial_body.translation);
-            let scale_matrix = Mat4::new_scaling(self.celestial_body.scale);
+// Point light, placed at the sun every frame
+#[derive(Clone, Copy, Debug)]
+pub struct Light {
+    pub position: Vec3,
+    pub intensity: f32,
+    pub ambient: f32,
+}
 
-            self.model_matrix = translation_matrix * scale_matrix * self_rotation_matrix;
+impl Default for Light {
+    fn default() -> Self {
+        Light {
+            position: Vec3::zeros(),
+            intensity: 1.2,
+            ambient: 0.2,
         }
     }
-    pub fn translate_model(&mut self, d_translation: &Vec3, d_rotation: &Vec3) {
-        self.celestial_body.rotation = self.celestial_body.rotation + d_rotation;
-        self.celestial_body.translation = self.celestial_body.translation + d_translation;
-
-        let translation = self.celestial_body.translation;
-        let rotation = self.celestial_body.rotation;
-        let (sin_x, cos_x) = rotation.x.sin_cos();
-        let (sin_y, cos_y) = rotation.y.sin_cos();
-        let (sin_z, cos_z) = rotation.z.sin_cos();
-
-        let rotation_matrix_x = Mat4::new(
-            1.0, 0.0, 0.0, 0.0, 0.0, cos_x, -sin_x, 0.0, 0.0, sin_x, cos_x, 0.0, 0.0, 0.0, 0.0, 1.0,
-        );
-        let rotation_matrix_y = Mat4::new(
-            cos_y, 0.0, sin_y, 0.0, 0.0, 1.0, 0.0, 0.0, -sin_y, 0.0, cos_y, 0.0, 0.0, 0.0, 0.0, 1.0,
-        );
-        let rotation_matrix_z = Mat4::new(
-            cos_z, -sin_z, 0.0, 0.0, sin_z, cos_z, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
-        );
-
-        let rotation_matrix = rotation_matrix_z * rotation_matrix_y * rotation_matrix_x;
+}
 
-        let transform_matrix = Mat4::new(
-            self.celestial_body.scale,
-            0.0,
-            0.0,
-            translation.x,
-            0.0,
-            self.celestial_body.scale,
-            0.0,
-            translation.y,
-            0.0,
-            0.0,
-            self.celestial_body.scale,
-            translation.z,
-            0.0,
-            0.0,
-            0.0,
-            1.0,
-        );
+#[derive(Clone, Copy, Debug)]
+pub struct RenderOptions {
+    pub clip_mode: ClipMode,
+    pub perspective_correct: bool,
+    pub filter_mode: FilterMode,
+}
 
-        self.model_matrix = transform_matrix * rotation_matrix;
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            clip_mode: ClipMode::Frustum,
+            perspective_correct: true,
+            filter_mode: FilterMode::Trilinear,
+        }
     }
 }
 
-pub fn render(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_array: &[Vertex]) {
-    // 1. Vertex shader stage
-    let mut shaded_vertices: Vec<Vertex> = Vec::new();
-    for vertex in vertex_array {
-        shaded_vertices.push(vertex_shader(vertex, uniforms))
+impl Uniforms {
+    pub fn set_view_matrix(&mut self, view_matrix: &Mat4) {
+        self.view_matrix = *view_matrix;
     }
-
-    // 2. Primitive Assembly stage (only triangles)
-    let mut triangles = Vec::new();
-    for i in (0..shaded_vertices.len()).step_by(3) {
-        if i + 2 < shaded_vertices.len() {
-            triangles.push([
-                shaded_vertices[i].clone(),
-                shaded_vertices[i + 1].clone(),
-                shaded_vertices[i + 2].clone(),
-            ]);
-        }
+    // Scene graph transform relative to the parent: the place on the orbit (or
+    // the fixed position) and the tilt. Children inherit it, spin and scale
+    // only apply to the body itself.
+    pub fn local_transform(&self) -> Mat4 {
+        let body = &self.celestial_body;
+        let position = match body.orbit {
+            Some(orbit) => orbit.position(self.time as f32),
+            None => body.position,
+        };
+        self.transform_at(&position)
     }
+    // Same, with the position given by someone else (the gravity mode)
+    pub fn transform_at(&self, position: &Vec3) -> Mat4 {
+        Mat4::new_translation(position) * create_rotation_matrix(&self.celestial_body.rotation)
+    }
+    // Puts the body where its scene graph node is and spins it around its own axis
+    pub fn place(&mut self, world: &Mat4) {
+        let year = self.celestial_body.orbit.map_or(0.0, |orbit| orbit.mean_motion);
+        let self_rotation_speed =
+            (self.celestial_body.day - year) * self.time as f32; // Example for slower self-rotation
 
-    // Rasterization Stage
+        let self_rotation_matrix = Mat4::from_axis_angle(&Vec3::y_axis(), self_rotation_speed);
+        let scale_matrix = Mat4::new_scaling(self.celestial_body.scale);
 
-    let mut fragments: Vec<Fragment> = Vec::new();
-    for tri in triangles {
-        fragments.extend(triangle_fill(&tri[0], &tri[1], &tri[2]));
+        self.celestial_body.translation = (world * Vec4::new(0.0, 0.0, 0.0, 1.0)).xyz();
+        self.model_matrix = world * self_rotation_matrix * scale_matrix;
     }
-    // Fragment Processing Stage
-    for fragment in fragments {
-        let x = fragment.position.x as usize;
-        let y = fragment.position.y as usize;
-        let looking_coeficient = fragment.normal.dot(&uniforms.looking_dir);
-        if looking_coeficient > 0.01 {
-            let shaded_color = fragment_shader(&fragment, uniforms);
-            framebuffer.set_current_color(Color::to_hex(&shaded_color));
-            framebuffer.point(x, y, fragment.depth);
+}
+
+pub fn render(framebuffer: &mut Framebuffer, uniforms: &Uniforms, mesh: &Mesh, surface: &Surface) {
+    // 1. Vertex shader stage, each unique vertex is shaded once into the post-transform cache
+    let vertex_cache: Vec<Vertex> = mesh
+        .vertices
+        .par_iter()
+        .map(|vertex| vertex_shader(vertex, uniforms))
+        .collect();
+
+    // 2. Primitive Assembly stage (only triangles), by index into the cache
+    // 3. Clipping stage, then perspective division into screen space
+    let mut screen_triangles = Vec::new();
+    for [i1, i2, i3] in mesh.triangles() {
+        let tri = [&vertex_cache[i1], &vertex_cache[i2], &vertex_cache[i3]];
+        let polygon = clip_triangle(tri, uniforms.render_options.clip_mode);
+        for mut clipped in triangulate(&polygon) {
+            for vertex in clipped.iter_mut() {
+                to_screen(vertex, &uniforms.viewport_matrix);
+            }
+
+            // 4. Face culling on the screen-space winding
+            let area = signed_area(&clipped);
+            if is_culled(area, uniforms.celestial_body.cull_mode) {
+                continue;
+            }
+            if area < 0.0 {
+                // Double-sided surfaces are lit from the side facing the camera
+                for vertex in clipped.iter_mut() {
+                    vertex.transformed_normal = -vertex.transformed_normal;
+                }
+            }
+            screen_triangles.push(clipped);
         }
     }
+
+    // 5. Binned rasterization and fragment processing, in parallel per tile
+    rasterize(
+        framebuffer,
+        &screen_triangles,
+        uniforms.render_options.perspective_correct,
+        surface.blend_mode,
+        |fragment| Some(fragment_shader(fragment, uniforms, surface)),
+    );
 }
@@ -1,14 +1,21 @@
-use nalgebra_glm::{Vec2, Vec3};
+use nalgebra_glm::{Vec2, Vec3, Vec4};
 use crate::screen::color::Color;
 
 #[derive(Clone, Debug)]
 pub struct Vertex {
   pub position: Vec3,
   pub normal: Vec3,
+  pub tangent: Vec3,
+  pub bitangent: Vec3,
   pub tex_coords: Vec2,
   pub color: Color,
+  pub world_position: Vec3,
+  pub clip_position: Vec4,
+  pub inv_w: f32,
   pub transformed_position: Vec3,
   pub transformed_normal: Vec3,
+  pub transformed_tangent: Vec3,
+  pub transformed_bitangent: Vec3,
 }
 
 // impl Vertex {
@@ -40,15 +47,43 @@ pub struct Vertex {
 //   }
 // }
 
+impl Vertex {
+  // Linear interpolation of every attribute, used when clipping creates new vertices
+  pub fn lerp(&self, other: &Vertex, t: f32) -> Vertex {
+    Vertex {
+      position: self.position.lerp(&other.position, t),
+      normal: self.normal.lerp(&other.normal, t),
+      tangent: self.tangent.lerp(&other.tangent, t),
+      bitangent: self.bitangent.lerp(&other.bitangent, t),
+      tex_coords: self.tex_coords.lerp(&other.tex_coords, t),
+      color: self.color.lerp(other.color, t),
+      world_position: self.world_position.lerp(&other.world_position, t),
+      clip_position: self.clip_position.lerp(&other.clip_position, t),
+      inv_w: self.inv_w + (other.inv_w - self.inv_w) * t,
+      transformed_position: self.transformed_position.lerp(&other.transformed_position, t),
+      transformed_normal: self.transformed_normal.lerp(&other.transformed_normal, t),
+      transformed_tangent: self.transformed_tangent.lerp(&other.transformed_tangent, t),
+      transformed_bitangent: self.transformed_bitangent.lerp(&other.transformed_bitangent, t),
+    }
+  }
+}
+
 impl Default for Vertex {
   fn default() -> Self {
     Vertex {
       position: Vec3::new(0.0, 0.0, 0.0),
       normal: Vec3::new(0.0, 1.0, 0.0),
+      tangent: Vec3::new(1.0, 0.0, 0.0),
+      bitangent: Vec3::new(0.0, 0.0, 1.0),
       tex_coords: Vec2::new(0.0, 0.0),
       color: Color::black(),
+      world_position: Vec3::new(0.0, 0.0, 0.0),
+      clip_position: Vec4::new(0.0, 0.0, 0.0, 1.0),
+      inv_w: 1.0,
       transformed_position: Vec3::new(0.0, 0.0, 0.0),
       transformed_normal: Vec3::new(0.0, 1.0, 0.0),
+      transformed_tangent: Vec3::new(1.0, 0.0, 0.0),
+      transformed_bitangent: Vec3::new(0.0, 0.0, 1.0),
     }
   }
 }
\ No newline at end of file